@@ -0,0 +1,127 @@
+#import bevy_pbr::{
+    mesh_bindings::mesh,
+    mesh_functions,
+    view_transformations::position_world_to_clip,
+}
+
+#ifdef PREPASS_PIPELINE
+#import bevy_pbr::prepass_io::VertexOutput
+#else
+#import bevy_pbr::forward_io::VertexOutput
+#endif
+
+struct CompactChunk {
+    biome_colors: array<vec4<f32>, 4>,
+}
+
+// Matches `CompactChunkRecord` in `material.rs`.
+struct ChunkRecord {
+    face_normal: vec3<f32>,
+    vertex_count: u32,
+    face_x: vec3<f32>,
+    radius: f32,
+    face_y: vec3<f32>,
+    // xy: minimum corner, zw: maximum corner, in face coordinates in [-1, 1].
+    bounds: vec4<f32>,
+    center: vec3<f32>,
+}
+
+@group(2) @binding(100) var<uniform> compact: CompactChunk;
+@group(2) @binding(101) var<storage, read> chunks: array<ChunkRecord>;
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @builtin(vertex_index) index: u32,
+    @location(0) height: f32,
+    @location(1) normal: vec2<f32>,
+#ifdef TERRAIN_BIOMES
+    @location(2) biome_weights: vec4<f32>,
+#endif
+    // Index of the chunk's record in `chunks`.
+    @location(3) slot: u32,
+};
+
+// Returns the grid coordinates of a vertex of a chunk with `n` vertices along each side. Skirt
+// vertices follow the grid vertices, one edge after another in the order north, east, south,
+// west.
+fn grid_coordinates(index: u32, n: u32) -> vec2<u32> {
+    if index < n * n {
+        return vec2(index % n, index / n);
+    }
+    let edge = (index - n * n) / n;
+    let i = (index - n * n) % n;
+    let last = n - 1u;
+    if edge == 0u {
+        return vec2(i, last);
+    } else if edge == 1u {
+        return vec2(last, i);
+    } else if edge == 2u {
+        return vec2(i, 0u);
+    }
+    return vec2(0u, i);
+}
+
+fn unit_cube_to_sphere(p: vec3<f32>) -> vec3<f32> {
+    let p2 = p * p;
+    return p * sqrt(1.0 - p2.yzx / 2.0 - p2.zxy / 2.0 + p2.yzx * p2.zxy / 3.0);
+}
+
+fn octahedral_decode(encoded: vec2<f32>) -> vec3<f32> {
+    var normal = vec3(encoded, 1.0 - abs(encoded.x) - abs(encoded.y));
+    let fold = max(-normal.z, 0.0);
+    normal.x += select(fold, -fold, normal.x >= 0.0);
+    normal.y += select(fold, -fold, normal.y >= 0.0);
+    return normalize(normal);
+}
+
+// Maps face coordinates in [-1, 1] to the face's UVs in [0, 1], like `face_uv` in `helpers.rs`.
+fn face_uv(face: vec2<f32>) -> vec2<f32> {
+    return (face + 1.0) / 2.0;
+}
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+
+    let chunk = chunks[vertex.slot];
+    let first_vertex = mesh[vertex.instance_index].first_vertex_index;
+    let grid = vec2<f32>(grid_coordinates(vertex.index - first_vertex, chunk.vertex_count));
+    let face = mix(chunk.bounds.xy, chunk.bounds.zw, grid / f32(chunk.vertex_count - 1u));
+    let direction = normalize(unit_cube_to_sphere(chunk.face_normal + chunk.face_x * face.x + chunk.face_y * face.y));
+    let position = direction * (chunk.radius + vertex.height) - chunk.center;
+    let normal = octahedral_decode(vertex.normal);
+
+    let world_from_local = mesh_functions::get_world_from_local(vertex.instance_index);
+    out.world_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4(position, 1.0));
+    out.position = position_world_to_clip(out.world_position.xyz);
+
+#ifdef PREPASS_PIPELINE
+#ifdef DEPTH_CLAMP_ORTHO
+    out.clip_position_unclamped = out.position;
+    out.position.z = min(out.position.z, 1.0);
+#endif
+#ifdef NORMAL_PREPASS_OR_DEFERRED_PREPASS
+    out.world_normal = mesh_functions::mesh_normal_local_to_world(normal, vertex.instance_index);
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    let previous_world_from_local = mesh_functions::get_previous_world_from_local(vertex.instance_index);
+    out.previous_world_position = mesh_functions::mesh_position_local_to_world(previous_world_from_local, vec4(position, 1.0));
+#endif
+#else
+    out.world_normal = mesh_functions::mesh_normal_local_to_world(normal, vertex.instance_index);
+#ifdef VERTEX_UVS_A
+    out.uv = face_uv(face);
+#endif
+#ifdef TERRAIN_BIOMES
+    out.color = compact.biome_colors[0] * vertex.biome_weights.x
+        + compact.biome_colors[1] * vertex.biome_weights.y
+        + compact.biome_colors[2] * vertex.biome_weights.z
+        + compact.biome_colors[3] * vertex.biome_weights.w;
+#endif
+#endif
+
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    out.instance_index = vertex.instance_index;
+#endif
+    return out;
+}
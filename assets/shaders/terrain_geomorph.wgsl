@@ -0,0 +1,157 @@
+#import bevy_pbr::{
+    mesh_functions,
+    forward_io::{VertexOutput, FragmentOutput},
+    pbr_bindings,
+    pbr_fragment::pbr_input_from_standard_material,
+    pbr_functions::{alpha_discard, apply_pbr_lighting, main_pass_post_lighting_processing},
+    pbr_types,
+    view_transformations::position_world_to_clip,
+}
+#import "shaders/terrain_morph.wgsl"::{MorphObservers, morph_factor, morph_normal, morph_tangent}
+
+struct Geomorph {
+    morph_start: f32,
+    observers: MorphObservers,
+    triplanar_scale: f32,
+    biome_colors: array<vec4<f32>, 4>,
+}
+
+@group(2) @binding(100) var<uniform> geomorph: Geomorph;
+
+// Matches `TEXTURE_PERIOD` in `mesh.rs`.
+const TEXTURE_PERIOD: f32 = 4096.0;
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+#ifdef VERTEX_TANGENTS
+    @location(4) tangent: vec4<f32>,
+#endif
+    // xyz: position in the parent LOD, w: observer distance at which the morph completes.
+    @location(8) morph: vec4<f32>,
+#ifdef TERRAIN_BIOMES
+    @location(9) biome_weights: vec4<f32>,
+#endif
+    // Position relative to the body, wrapped to the texture period.
+    @location(11) texture_position: vec3<f32>,
+    // Normal in the parent LOD.
+    @location(12) morph_normal: vec3<f32>,
+};
+
+// Bevy's `VertexOutput`, plus the body-space position and normal that textures are projected
+// along.
+struct TerrainVertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) world_position: vec4<f32>,
+    @location(1) world_normal: vec3<f32>,
+#ifdef VERTEX_UVS_A
+    @location(2) uv: vec2<f32>,
+#endif
+#ifdef VERTEX_TANGENTS
+    @location(4) world_tangent: vec4<f32>,
+#endif
+#ifdef VERTEX_COLORS
+    @location(5) color: vec4<f32>,
+#endif
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    @location(6) @interpolate(flat) instance_index: u32,
+#endif
+    @location(8) texture_position: vec3<f32>,
+    @location(9) texture_normal: vec3<f32>,
+}
+
+fn vertex_output(in: TerrainVertexOutput) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = in.position;
+    out.world_position = in.world_position;
+    out.world_normal = in.world_normal;
+#ifdef VERTEX_UVS_A
+    out.uv = in.uv;
+#endif
+#ifdef VERTEX_TANGENTS
+    out.world_tangent = in.world_tangent;
+#endif
+#ifdef VERTEX_COLORS
+    out.color = in.color;
+#endif
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    out.instance_index = in.instance_index;
+#endif
+    return out;
+}
+
+@vertex
+fn vertex(vertex: Vertex) -> TerrainVertexOutput {
+    var out: TerrainVertexOutput;
+
+    let world_from_local = mesh_functions::get_world_from_local(vertex.instance_index);
+    let fine_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4(vertex.position, 1.0));
+    let morph = morph_factor(geomorph.observers, geomorph.morph_start, fine_position.xyz, vertex.morph.w);
+    let position = mix(vertex.position, vertex.morph.xyz, morph);
+    let normal = morph_normal(vertex.normal, vertex.morph_normal, morph);
+
+    out.world_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4(position, 1.0));
+    out.position = position_world_to_clip(out.world_position.xyz);
+    out.world_normal = mesh_functions::mesh_normal_local_to_world(normal, vertex.instance_index);
+    // Chunks are not rotated within their body, so their local space is the body's.
+    out.texture_position = vertex.texture_position + position - vertex.position;
+    out.texture_normal = normal;
+#ifdef VERTEX_UVS_A
+    out.uv = vertex.uv;
+#endif
+#ifdef TERRAIN_BIOMES
+    out.color = geomorph.biome_colors[0] * vertex.biome_weights.x
+        + geomorph.biome_colors[1] * vertex.biome_weights.y
+        + geomorph.biome_colors[2] * vertex.biome_weights.z
+        + geomorph.biome_colors[3] * vertex.biome_weights.w;
+#endif
+#ifdef VERTEX_TANGENTS
+    out.world_tangent = mesh_functions::mesh_tangent_local_to_world(world_from_local, morph_tangent(vertex.tangent, normal), vertex.instance_index);
+#endif
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    out.instance_index = vertex.instance_index;
+#endif
+    return out;
+}
+
+// Samples the base color texture along the three axes of the body, blended by how squarely the
+// surface faces each of them. The texture repeats a whole number of times per texture period,
+// so it continues where chunks wrap to different periods.
+fn triplanar_base_color(position: vec3<f32>, normal: vec3<f32>) -> vec4<f32> {
+    let repeats = max(round(geomorph.triplanar_scale * TEXTURE_PERIOD), 1.0);
+    let p = position * (repeats / TEXTURE_PERIOD);
+    var weights = pow(abs(normal), vec3(4.0));
+    weights /= weights.x + weights.y + weights.z;
+    return textureSample(pbr_bindings::base_color_texture, pbr_bindings::base_color_sampler, p.zy) * weights.x
+        + textureSample(pbr_bindings::base_color_texture, pbr_bindings::base_color_sampler, p.xz) * weights.y
+        + textureSample(pbr_bindings::base_color_texture, pbr_bindings::base_color_sampler, p.xy) * weights.z;
+}
+
+@fragment
+fn fragment(terrain: TerrainVertexOutput, @builtin(front_facing) is_front: bool) -> FragmentOutput {
+    let in = vertex_output(terrain);
+    var pbr_input = pbr_input_from_standard_material(in, is_front);
+
+#ifdef TERRAIN_TRIPLANAR
+    if (pbr_bindings::material.flags & pbr_types::STANDARD_MATERIAL_FLAGS_BASE_COLOR_TEXTURE_BIT) != 0u {
+        pbr_input.material.base_color = pbr_bindings::material.base_color
+            * triplanar_base_color(terrain.texture_position, normalize(terrain.texture_normal));
+#ifdef VERTEX_COLORS
+        pbr_input.material.base_color *= in.color;
+#endif
+    }
+#endif
+
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+    var out: FragmentOutput;
+    if (pbr_input.material.flags & pbr_types::STANDARD_MATERIAL_FLAGS_UNLIT_BIT) == 0u {
+        out.color = apply_pbr_lighting(pbr_input);
+    } else {
+        out.color = pbr_input.material.base_color;
+    }
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+    return out;
+}
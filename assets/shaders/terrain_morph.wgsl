@@ -0,0 +1,39 @@
+// Geomorphing shared by the terrain materials' vertex shaders.
+
+struct MorphObserver {
+    position: vec3<f32>,
+    radius: f32,
+    weight: f32,
+}
+
+// Matches `MorphObservers` in `material.rs`.
+struct MorphObservers {
+    count: u32,
+    observers: array<MorphObserver, 8>,
+}
+
+// Returns how far a vertex at `world_position` has morphed toward the parent LOD, from its
+// distance to the nearest observer as the cube tree measures it. The morph starts at
+// `morph_start` times the vertex's `morph_distance` and completes at the full distance.
+fn morph_factor(observers: MorphObservers, morph_start: f32, world_position: vec3<f32>, morph_distance: f32) -> f32 {
+    // A local copy can be indexed dynamically.
+    var list = observers;
+    var distance = 3.4e38;
+    for (var i = 0u; i < list.count; i++) {
+        let observer = list.observers[i];
+        let observer_distance = (length(world_position - observer.position) - observer.radius)
+            / max(observer.weight, 1e-6);
+        distance = min(distance, observer_distance);
+    }
+    return smoothstep(morph_start * morph_distance, morph_distance, distance);
+}
+
+// Returns the normal blended toward the parent LOD's.
+fn morph_normal(normal: vec3<f32>, parent_normal: vec3<f32>, morph: f32) -> vec3<f32> {
+    return normalize(mix(normal, parent_normal, morph));
+}
+
+// Returns the tangent made perpendicular to a morphed normal again.
+fn morph_tangent(tangent: vec4<f32>, normal: vec3<f32>) -> vec4<f32> {
+    return vec4(normalize(tangent.xyz - normal * dot(tangent.xyz, normal)), tangent.w);
+}
@@ -0,0 +1,79 @@
+#import bevy_pbr::{
+    mesh_functions,
+    forward_io::{VertexOutput, FragmentOutput},
+    pbr_fragment::pbr_input_from_standard_material,
+    pbr_functions::{alpha_discard, apply_pbr_lighting, main_pass_post_lighting_processing},
+    pbr_types,
+    view_transformations::position_world_to_clip,
+}
+#import "shaders/terrain_morph.wgsl"::{MorphObservers, morph_factor, morph_normal, morph_tangent}
+
+struct Water {
+    morph_start: f32,
+    observers: MorphObservers,
+    shallow_color: vec4<f32>,
+    deep_color: vec4<f32>,
+    depth_scale: f32,
+}
+
+@group(2) @binding(100) var<uniform> water: Water;
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+#ifdef VERTEX_TANGENTS
+    @location(4) tangent: vec4<f32>,
+#endif
+    // xyz: position in the parent LOD, w: observer distance at which the morph completes.
+    @location(8) morph: vec4<f32>,
+    // Depth of the terrain below the water surface.
+    @location(11) depth: f32,
+    // Normal in the parent LOD.
+    @location(12) morph_normal: vec3<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+
+    let world_from_local = mesh_functions::get_world_from_local(vertex.instance_index);
+    let fine_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4(vertex.position, 1.0));
+    let morph = morph_factor(water.observers, water.morph_start, fine_position.xyz, vertex.morph.w);
+    let position = mix(vertex.position, vertex.morph.xyz, morph);
+    let normal = morph_normal(vertex.normal, vertex.morph_normal, morph);
+
+    out.world_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4(position, 1.0));
+    out.position = position_world_to_clip(out.world_position.xyz);
+    out.world_normal = mesh_functions::mesh_normal_local_to_world(normal, vertex.instance_index);
+#ifdef VERTEX_UVS_A
+    out.uv = vertex.uv;
+#endif
+    out.uv_b = vec2(vertex.depth, 0.0);
+#ifdef VERTEX_TANGENTS
+    out.world_tangent = mesh_functions::mesh_tangent_local_to_world(world_from_local, morph_tangent(vertex.tangent, normal), vertex.instance_index);
+#endif
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    out.instance_index = vertex.instance_index;
+#endif
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput, @builtin(front_facing) is_front: bool) -> FragmentOutput {
+    var pbr_input = pbr_input_from_standard_material(in, is_front);
+
+    let deep = 1.0 - exp(-max(in.uv_b.x, 0.0) / max(water.depth_scale, 1e-6));
+    pbr_input.material.base_color *= mix(water.shallow_color, water.deep_color, deep);
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+    var out: FragmentOutput;
+    if (pbr_input.material.flags & pbr_types::STANDARD_MATERIAL_FLAGS_UNLIT_BIT) == 0u {
+        out.color = apply_pbr_lighting(pbr_input);
+    } else {
+        out.color = pbr_input.material.base_color;
+    }
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+    return out;
+}
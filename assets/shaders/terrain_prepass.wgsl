@@ -0,0 +1,68 @@
+// The prepass and shadow vertex shader of the geomorphing terrain materials, which places
+// vertices where their main pass does.
+
+#import bevy_pbr::{
+    mesh_functions,
+    prepass_io::VertexOutput,
+    view_transformations::position_world_to_clip,
+}
+#import "shaders/terrain_morph.wgsl"::{MorphObservers, morph_factor, morph_normal, morph_tangent}
+
+// The fields every geomorphing terrain material's uniform starts with.
+struct TerrainMorph {
+    morph_start: f32,
+    observers: MorphObservers,
+}
+
+@group(2) @binding(100) var<uniform> terrain: TerrainMorph;
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>,
+#ifdef VERTEX_UVS_A
+    @location(1) uv: vec2<f32>,
+#endif
+#ifdef NORMAL_PREPASS_OR_DEFERRED_PREPASS
+    @location(3) normal: vec3<f32>,
+#ifdef VERTEX_TANGENTS
+    @location(4) tangent: vec4<f32>,
+#endif
+#endif
+    @location(8) morph: vec4<f32>,
+    @location(12) morph_normal: vec3<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+
+    let world_from_local = mesh_functions::get_world_from_local(vertex.instance_index);
+    let fine_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4(vertex.position, 1.0));
+    let morph = morph_factor(terrain.observers, terrain.morph_start, fine_position.xyz, vertex.morph.w);
+    let position = mix(vertex.position, vertex.morph.xyz, morph);
+
+    out.world_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4(position, 1.0));
+    out.position = position_world_to_clip(out.world_position.xyz);
+#ifdef DEPTH_CLAMP_ORTHO
+    out.clip_position_unclamped = out.position;
+    out.position.z = min(out.position.z, 1.0);
+#endif
+#ifdef VERTEX_UVS_A
+    out.uv = vertex.uv;
+#endif
+#ifdef NORMAL_PREPASS_OR_DEFERRED_PREPASS
+    let normal = morph_normal(vertex.normal, vertex.morph_normal, morph);
+    out.world_normal = mesh_functions::mesh_normal_local_to_world(normal, vertex.instance_index);
+#ifdef VERTEX_TANGENTS
+    out.world_tangent = mesh_functions::mesh_tangent_local_to_world(world_from_local, morph_tangent(vertex.tangent, normal), vertex.instance_index);
+#endif
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    let previous_world_from_local = mesh_functions::get_previous_world_from_local(vertex.instance_index);
+    out.previous_world_position = mesh_functions::mesh_position_local_to_world(previous_world_from_local, vec4(position, 1.0));
+#endif
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    out.instance_index = vertex.instance_index;
+#endif
+    return out;
+}
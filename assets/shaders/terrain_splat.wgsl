@@ -0,0 +1,253 @@
+#import bevy_pbr::{
+    mesh_functions,
+    forward_io::{VertexOutput, FragmentOutput},
+    pbr_bindings,
+    pbr_fragment::pbr_input_from_standard_material,
+    pbr_functions::{alpha_discard, apply_pbr_lighting, main_pass_post_lighting_processing},
+    pbr_types,
+    view_transformations::position_world_to_clip,
+}
+#import "shaders/terrain_morph.wgsl"::{MorphObservers, morph_factor, morph_normal, morph_tangent}
+
+struct SplatLayer {
+    tint: vec4<f32>,
+    biomes: vec4<f32>,
+    altitude: vec2<f32>,
+    slope: vec2<f32>,
+    altitude_blend: f32,
+    slope_blend: f32,
+}
+
+struct Splat {
+    morph_start: f32,
+    observers: MorphObservers,
+    triplanar_scale: f32,
+    layer_count: u32,
+    layers: array<SplatLayer, 8>,
+}
+
+@group(2) @binding(100) var<uniform> splat: Splat;
+@group(2) @binding(101) var albedo_texture: texture_2d_array<f32>;
+@group(2) @binding(102) var albedo_sampler: sampler;
+@group(2) @binding(103) var normal_map_texture: texture_2d_array<f32>;
+@group(2) @binding(104) var normal_map_sampler: sampler;
+
+// Matches `TEXTURE_PERIOD` in `mesh.rs`.
+const TEXTURE_PERIOD: f32 = 4096.0;
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+#ifdef VERTEX_TANGENTS
+    @location(4) tangent: vec4<f32>,
+#endif
+    // xyz: position in the parent LOD, w: observer distance at which the morph completes.
+    @location(8) morph: vec4<f32>,
+#ifdef TERRAIN_BIOMES
+    @location(9) biome_weights: vec4<f32>,
+#endif
+    // x: altitude above the body's radius, y: slope in radians.
+    @location(10) altitude_slope: vec2<f32>,
+    // Position relative to the body, wrapped to the texture period.
+    @location(11) texture_position: vec3<f32>,
+    // Normal in the parent LOD.
+    @location(12) morph_normal: vec3<f32>,
+};
+
+// Bevy's `VertexOutput`, plus the body-space position and normal that layers are projected
+// along.
+struct TerrainVertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) world_position: vec4<f32>,
+    @location(1) world_normal: vec3<f32>,
+#ifdef VERTEX_UVS_A
+    @location(2) uv: vec2<f32>,
+#endif
+    @location(3) uv_b: vec2<f32>,
+#ifdef VERTEX_TANGENTS
+    @location(4) world_tangent: vec4<f32>,
+#endif
+#ifdef VERTEX_COLORS
+    @location(5) color: vec4<f32>,
+#endif
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    @location(6) @interpolate(flat) instance_index: u32,
+#endif
+    @location(8) texture_position: vec3<f32>,
+    @location(9) texture_normal: vec3<f32>,
+}
+
+fn vertex_output(in: TerrainVertexOutput) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = in.position;
+    out.world_position = in.world_position;
+    out.world_normal = in.world_normal;
+#ifdef VERTEX_UVS_A
+    out.uv = in.uv;
+#endif
+    out.uv_b = in.uv_b;
+#ifdef VERTEX_TANGENTS
+    out.world_tangent = in.world_tangent;
+#endif
+#ifdef VERTEX_COLORS
+    out.color = in.color;
+#endif
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    out.instance_index = in.instance_index;
+#endif
+    return out;
+}
+
+@vertex
+fn vertex(vertex: Vertex) -> TerrainVertexOutput {
+    var out: TerrainVertexOutput;
+
+    let world_from_local = mesh_functions::get_world_from_local(vertex.instance_index);
+    let fine_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4(vertex.position, 1.0));
+    let morph = morph_factor(splat.observers, splat.morph_start, fine_position.xyz, vertex.morph.w);
+    let position = mix(vertex.position, vertex.morph.xyz, morph);
+    let normal = morph_normal(vertex.normal, vertex.morph_normal, morph);
+
+    out.world_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4(position, 1.0));
+    out.position = position_world_to_clip(out.world_position.xyz);
+    out.world_normal = mesh_functions::mesh_normal_local_to_world(normal, vertex.instance_index);
+    // Chunks are not rotated within their body, so their local space is the body's.
+    out.texture_position = vertex.texture_position + position - vertex.position;
+    out.texture_normal = normal;
+#ifdef VERTEX_UVS_A
+    out.uv = vertex.uv;
+#endif
+    out.uv_b = vertex.altitude_slope;
+#ifdef TERRAIN_BIOMES
+    out.color = vertex.biome_weights;
+#endif
+#ifdef VERTEX_TANGENTS
+    out.world_tangent = mesh_functions::mesh_tangent_local_to_world(world_from_local, morph_tangent(vertex.tangent, normal), vertex.instance_index);
+#endif
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    out.instance_index = vertex.instance_index;
+#endif
+    return out;
+}
+
+// Returns one within `range`, falling linearly to zero `blend` beyond either end.
+fn ramp(range: vec2<f32>, blend: f32, value: f32) -> f32 {
+    let outside = max(max(range.x - value, value - range.y), 0.0);
+    return saturate(1.0 - outside / max(blend, 1e-6));
+}
+
+fn layer_weight(layer: SplatLayer, altitude: f32, slope: f32, biome_weights: vec4<f32>) -> f32 {
+    return saturate(dot(layer.biomes, biome_weights))
+        * ramp(layer.altitude, layer.altitude_blend, altitude)
+        * ramp(layer.slope, layer.slope_blend, slope);
+}
+
+// Texture coordinates and their screen-space derivatives for the projections along the x, y
+// and z axes. Derivatives are taken up front, so layers can be skipped without breaking them.
+struct Triplanar {
+    x: vec2<f32>,
+    y: vec2<f32>,
+    z: vec2<f32>,
+    x_ddx: vec2<f32>,
+    x_ddy: vec2<f32>,
+    y_ddx: vec2<f32>,
+    y_ddy: vec2<f32>,
+    z_ddx: vec2<f32>,
+    z_ddy: vec2<f32>,
+    // How squarely the surface faces each axis, summing to one.
+    weights: vec3<f32>,
+}
+
+// Layers repeat a whole number of times per texture period, so they continue where chunks wrap
+// to different periods.
+fn triplanar(position: vec3<f32>, normal: vec3<f32>) -> Triplanar {
+    let repeats = max(round(splat.triplanar_scale * TEXTURE_PERIOD), 1.0);
+    let p = position * (repeats / TEXTURE_PERIOD);
+    var t: Triplanar;
+    t.x = p.zy;
+    t.y = p.xz;
+    t.z = p.xy;
+    t.x_ddx = dpdx(t.x);
+    t.x_ddy = dpdy(t.x);
+    t.y_ddx = dpdx(t.y);
+    t.y_ddy = dpdy(t.y);
+    t.z_ddx = dpdx(t.z);
+    t.z_ddy = dpdy(t.z);
+    let weights = pow(abs(normal), vec3(4.0));
+    t.weights = weights / (weights.x + weights.y + weights.z);
+    return t;
+}
+
+fn sample_albedo(t: Triplanar, layer: u32) -> vec4<f32> {
+    return textureSampleGrad(albedo_texture, albedo_sampler, t.x, layer, t.x_ddx, t.x_ddy) * t.weights.x
+        + textureSampleGrad(albedo_texture, albedo_sampler, t.y, layer, t.y_ddx, t.y_ddy) * t.weights.y
+        + textureSampleGrad(albedo_texture, albedo_sampler, t.z, layer, t.z_ddx, t.z_ddy) * t.weights.z;
+}
+
+// Returns the body-space normal of a layer, blending the tangent-space normals of the three
+// projections onto the surface normal with a whiteout blend.
+fn sample_normal(t: Triplanar, layer: u32, normal: vec3<f32>) -> vec3<f32> {
+    var x = textureSampleGrad(normal_map_texture, normal_map_sampler, t.x, layer, t.x_ddx, t.x_ddy).xyz * 2.0 - 1.0;
+    var y = textureSampleGrad(normal_map_texture, normal_map_sampler, t.y, layer, t.y_ddx, t.y_ddy).xyz * 2.0 - 1.0;
+    var z = textureSampleGrad(normal_map_texture, normal_map_sampler, t.z, layer, t.z_ddx, t.z_ddy).xyz * 2.0 - 1.0;
+    x = vec3(x.xy + normal.zy, abs(x.z) * normal.x);
+    y = vec3(y.xy + normal.xz, abs(y.z) * normal.y);
+    z = vec3(z.xy + normal.xy, abs(z.z) * normal.z);
+    return normalize(x.zyx * t.weights.x + y.xzy * t.weights.y + z.xyz * t.weights.z);
+}
+
+@fragment
+fn fragment(terrain: TerrainVertexOutput, @builtin(front_facing) is_front: bool) -> FragmentOutput {
+    let in = vertex_output(terrain);
+    var pbr_input = pbr_input_from_standard_material(in, is_front);
+
+#ifdef TERRAIN_BIOMES
+    let biome_weights = in.color;
+#else
+    let biome_weights = vec4(1.0);
+#endif
+    var weights: array<f32, 8>;
+    var total = 0.0;
+    for (var i = 0u; i < splat.layer_count; i++) {
+        weights[i] = layer_weight(splat.layers[i], in.uv_b.x, in.uv_b.y, biome_weights);
+        total += weights[i];
+    }
+    // Points no layer covers fall back to the first layer.
+    if total <= 0.0 {
+        weights[0] = 1.0;
+        total = 1.0;
+    }
+
+    // Layers are projected in the body's space, so they stay put as the body moves and turns.
+    let normal = normalize(terrain.texture_normal);
+    let t = triplanar(terrain.texture_position, normal);
+    var albedo = vec4(0.0);
+    var layer_normal = vec3(0.0);
+    for (var i = 0u; i < max(splat.layer_count, 1u); i++) {
+        let weight = weights[i] / total;
+        if weight <= 0.0 {
+            continue;
+        }
+        albedo += sample_albedo(t, i) * splat.layers[i].tint * weight;
+#ifdef SPLAT_NORMAL_MAP
+        layer_normal += sample_normal(t, i, normal) * weight;
+#endif
+    }
+    pbr_input.material.base_color = pbr_bindings::material.base_color * albedo;
+#ifdef SPLAT_NORMAL_MAP
+    pbr_input.N = mesh_functions::mesh_normal_local_to_world(normalize(layer_normal), in.instance_index);
+#endif
+
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+    var out: FragmentOutput;
+    if (pbr_input.material.flags & pbr_types::STANDARD_MATERIAL_FLAGS_UNLIT_BIT) == 0u {
+        out.color = apply_pbr_lighting(pbr_input);
+    } else {
+        out.color = pbr_input.material.base_color;
+    }
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+    return out;
+}
@@ -1,12 +1,10 @@
-#![allow(incomplete_features)]
-#![feature(generic_const_exprs)]
-
 use avian3d::math::{Scalar, Vector2};
 use bevy::pbr::wireframe::{WireframeConfig, WireframePlugin};
 use bevy::prelude::*;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use bevy_panorbit_camera::{PanOrbitCamera, PanOrbitCameraPlugin};
 use procedural_planet::plugins::terrain::cube_tree::ChunkData;
+use procedural_planet::plugins::terrain::Heightfield;
 use procedural_planet::{
     materials::GlobalMaterialsPlugin,
     math::Rectangle,
@@ -40,10 +38,11 @@ fn setup(
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
     const RADIUS: Scalar = 10.0;
-    let mes_builder = ChunkMeshBuilder::<5>::new(RADIUS);
+    let height = Heightfield::default();
+    let mes_builder = ChunkMeshBuilder::new(RADIUS, height.clone()).with_subdivisions(5);
     for axis in Axis::ALL {
         let bounds = Rectangle::from_corners(Vector2::new(-10.0, -10.0), Vector2::new(10.0, 10.0));
-        let mesh = mes_builder.build(&bounds, &ChunkData::new_root(axis, &bounds, 10.0));
+        let mesh = mes_builder.build(&bounds, &ChunkData::new_root(axis, &bounds, 10.0, &*height));
         let mesh_handle = meshes.add(mesh);
         let material_handle = materials.add(StandardMaterial::from_color(Color::srgb_from_array(
             axis.to_array_f32(),
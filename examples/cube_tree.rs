@@ -1,6 +1,3 @@
-#![allow(incomplete_features)]
-#![feature(generic_const_exprs)]
-
 use avian3d::math::{Scalar, Vector};
 use bevy::color::palettes::basic::FUCHSIA;
 use bevy::pbr::wireframe::{WireframeConfig, WireframePlugin};
@@ -8,6 +5,7 @@ use bevy::prelude::*;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use bevy_panorbit_camera::{PanOrbitCamera, PanOrbitCameraPlugin};
 use procedural_planet::plugins::terrain::cube_tree::CubeTree;
+use procedural_planet::plugins::terrain::Heightfield;
 use procedural_planet::{
     materials::GlobalMaterialsPlugin,
     plugins::terrain::{cube_tree::Axis, mesh::ChunkMeshBuilder},
@@ -64,10 +62,11 @@ fn setup(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    let mut tree = CubeTree::new(RADIUS);
+    let mut tree = CubeTree::new(RADIUS, Heightfield::default());
     let point = (Vector::Y + Vector::X + Vector::Z) * RADIUS * 0.56;
     tree.insert(point);
-    let mesh_builder = ChunkMeshBuilder::<SUBDIVISIONS>::new(RADIUS);
+    let mesh_builder =
+        ChunkMeshBuilder::new(RADIUS, tree.height.clone()).with_subdivisions(SUBDIVISIONS);
     let materials = Axis::ALL.map(|axis| {
         #[cfg(feature = "f64")]
         let material = StandardMaterial::from_color(Color::srgb_from_array(axis.to_array_f32()));
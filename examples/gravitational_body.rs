@@ -20,7 +20,7 @@ fn main() {
         .add_plugins(GlobalMaterialsPlugin)
         .add_plugins(PhysicsPlugin::default())
         .add_plugins(BigSpacePlugin::<Precision>::default())
-        .add_plugins(TerrainPlugin::<Player, 6>::default())
+        .add_plugins(TerrainPlugin::<Player>::default().with_subdivisions(6))
         .add_plugins(PlayerPlugin)
         .insert_resource(ClearColor(Color::BLACK))
         .insert_resource(AmbientLight {
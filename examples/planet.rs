@@ -1,6 +1,3 @@
-#![allow(incomplete_features)]
-#![feature(generic_const_exprs)]
-
 use avian3d::math::*;
 use bevy::color::palettes::css::{DARK_SEA_GREEN, INDIAN_RED};
 use bevy::pbr::wireframe::{WireframeConfig, WireframePlugin};
@@ -19,6 +16,8 @@ use procedural_planet::plugins::terrain::{Body, BodyPreset, GenerateMeshes, Radi
 use procedural_planet::plugins::terrain::mesh::ChunkMeshBuilder;
 // use procedural_planet::plugins::TerrainPlugin;
 
+const SUBDIVISIONS: usize = 5;
+
 fn main() {
     let mut app = App::new();
     app.add_plugins(DefaultPlugins)
@@ -49,7 +48,7 @@ fn main() {
                 grab_ungrab_mouse,
             ),
         )
-        .add_observer(generate_meshes::<5>);
+        .add_observer(generate_meshes);
 
     app.run();
 }
@@ -194,17 +193,14 @@ impl FromWorld for ChunkMaterials {
 }
 
 #[allow(clippy::type_complexity)]
-fn generate_meshes<const SUBDIVISIONS: usize>(
+fn generate_meshes(
     trigger: Trigger<GenerateMeshes>,
     mut commands: Commands,
     planet_query: Query<(&CubeTree, &Grid<i64>, &GridCell<i64>, &Transform), With<Body>>,
     mut spawned_chunks: Local<Vec<Entity>>,
     mut meshes: ResMut<Assets<Mesh>>,
     materials: Res<ChunkMaterials>,
-) where
-    [(); (SUBDIVISIONS + 2).pow(2)]:,
-    [(); (SUBDIVISIONS + 1).pow(2) * 6]:,
-{
+) {
     let body_entity = trigger.entity();
     let Ok((cube_tree, grid, grid_cell, transform)) = planet_query.get(body_entity) else {
         return;
@@ -216,7 +212,8 @@ fn generate_meshes<const SUBDIVISIONS: usize>(
 
     let mut hash_set: HashSet<ChunkHash> = HashSet::with_capacity(cube_tree.iter().count());
 
-    let mesh_builder = ChunkMeshBuilder::<SUBDIVISIONS>::new(cube_tree.radius);
+    let mesh_builder = ChunkMeshBuilder::new(cube_tree.radius, cube_tree.height.clone())
+        .with_subdivisions(SUBDIVISIONS);
     let planet_pos = grid.grid_position_double(grid_cell, transform);
     commands.entity(body_entity).with_children(|parent| {
         for (&bounds, &data) in cube_tree.iter() {
@@ -1,6 +1,3 @@
-#![allow(incomplete_features)]
-#![feature(generic_const_exprs)]
-
 use avian3d::math::*;
 use bevy::pbr::wireframe::{WireframeConfig, WireframePlugin};
 use bevy::pbr::CascadeShadowConfigBuilder;
@@ -23,7 +20,7 @@ fn main() {
             BigSpacePlugin::<i64>::default(),
             FloatingOriginDebugPlugin::<i64>::default(),
             GlobalMaterialsPlugin,
-            TerrainPlugin::<PlayerCamera, 5>::default(),
+            TerrainPlugin::<PlayerCamera>::default().with_subdivisions(5),
             CameraControllerPlugin::<i64>::default(),
         ))
         .insert_resource(WireframeConfig {
@@ -83,7 +80,10 @@ fn setup(mut commands: Commands) {
             let body_preset = BodyPreset::EARTH / 10.0;
             camera_pos = Vector::X * body_preset.radius * 1.2;
 
-            planet.insert((Body::from_preset(body_preset), Name::new("Planet")));
+            planet.insert((
+                Body::from_preset(body_preset).with_sea_level(0.0),
+                Name::new("Planet"),
+            ));
 
             let (camera_cell, camera_translation) = planet.grid().translation_to_grid(camera_pos);
 
@@ -9,3 +9,5 @@ pub const EARTH_GRAVITATIONAL_ACCELERATION: Scalar = 9.81;
 pub const MOON_MASS_KG: Scalar = 7.347e22;
 pub const MOON_DIAMETER_M: Scalar = 1_737_100.0;
 pub const MOON_GRAVITATIONAL_ACCELERATION: Scalar = 1.625;
+
+pub const SEAWATER_DENSITY_KG_M3: Scalar = 1025.0;
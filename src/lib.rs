@@ -1,6 +1,3 @@
-#![allow(incomplete_features)]
-#![feature(generic_const_exprs)]
-
 pub mod constants;
 pub mod keybinds;
 pub mod materials;
@@ -13,7 +10,6 @@ use bevy::prelude::*;
 use big_space::camera::CameraController;
 use big_space::{camera::CameraControllerPlugin, prelude::*};
 
-use constants::terrain::CHUNK_SUBDIVISIONS;
 use materials::GlobalMaterialsPlugin;
 use plugins::{
     terrain::body::{Body, BodyPreset},
@@ -43,7 +39,7 @@ impl Plugin for GamePlugin {
                 AssetLoaderPlugin,
                 PlayerPlugin,
                 GlobalMaterialsPlugin,
-                TerrainPlugin::<OrbitCamera, CHUNK_SUBDIVISIONS>::default(),
+                TerrainPlugin::<OrbitCamera>::default(),
                 CameraControllerPlugin::<Precision>::default(),
             ))
             .add_systems(Startup, setup);
@@ -30,6 +30,22 @@ impl From<u32> for Quadrant {
     }
 }
 
+/// Leaves created and removed by [`QuadTreeNode::update`], in the order the changes were made.
+#[derive(Clone, Debug)]
+pub struct QuadTreeDiff<T> {
+    pub added: Vec<(Rectangle, T)>,
+    pub removed: Vec<(Rectangle, T)>,
+}
+
+impl<T> Default for QuadTreeDiff<T> {
+    fn default() -> Self {
+        Self {
+            added: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+}
+
 /// A memory-efficient quadtree node that can either be an internal node with four children
 /// or a leaf node containing generic data that implements Copy + Clone.
 #[derive(Clone)]
@@ -132,6 +148,88 @@ impl<T: Clone> QuadTreeNode<T> {
         }
     }
 
+    /// Updates the tree in place, splitting leaves rejected by `predicate` and collapsing internal
+    /// nodes back into a leaf when `merge` accepts their bounds.
+    ///
+    /// `predicate` returns `true` to keep a node as a leaf, as in [`QuadTreeNode::insert_mut`].
+    /// `merge` should agree with what `predicate` decides for the merged leaf, which is only
+    /// given data from `merge_data` once merged, so deciding stays cheap. Nodes whose decision
+    /// did not change are left untouched; every leaf created or removed is recorded in `diff`.
+    pub fn update<P, F, G, M>(
+        &mut self,
+        mut predicate: P,
+        create_data: F,
+        mut merge: G,
+        merge_data: M,
+        diff: &mut QuadTreeDiff<T>,
+    ) where
+        P: FnMut(PredicateMutArgs<T>) -> bool,
+        F: Fn(CreateDataArgs<T>) -> T,
+        G: FnMut(&Rectangle) -> bool,
+        M: Fn(&Rectangle) -> T,
+    {
+        self.update_impl(
+            &mut predicate,
+            &create_data,
+            &mut merge,
+            &merge_data,
+            false,
+            diff,
+        )
+    }
+
+    fn update_impl<P, F, G, M>(
+        &mut self,
+        predicate: &mut P,
+        create_data: &F,
+        merge: &mut G,
+        merge_data: &M,
+        created: bool,
+        diff: &mut QuadTreeDiff<T>,
+    ) where
+        P: FnMut(PredicateMutArgs<T>) -> bool,
+        F: Fn(CreateDataArgs<T>) -> T,
+        G: FnMut(&Rectangle) -> bool,
+        M: Fn(&Rectangle) -> T,
+    {
+        match self {
+            QuadTreeNode::Internal { bounds, children } => {
+                if merge(bounds) {
+                    let data = merge_data(bounds);
+                    for child in children.iter() {
+                        diff.removed
+                            .extend(child.iter().map(|(bounds, data)| (*bounds, data.clone())));
+                    }
+                    diff.added.push((*bounds, data.clone()));
+                    *self = Self::new(*bounds, data);
+                    return;
+                }
+                for child in children {
+                    child.update_impl(predicate, create_data, merge, merge_data, created, diff);
+                }
+            }
+            QuadTreeNode::Leaf { bounds, data } => {
+                if predicate((&*bounds, data)) {
+                    if created {
+                        diff.added.push((*bounds, data.clone()));
+                    }
+                    return;
+                }
+                if !created {
+                    diff.removed.push((*bounds, data.clone()));
+                }
+                self.subdivided(create_data).update_impl(
+                    predicate,
+                    create_data,
+                    merge,
+                    merge_data,
+                    true,
+                    diff,
+                );
+            }
+        }
+    }
+
     /// Returns the bounds of this node.
     #[inline]
     pub fn bounds(&self) -> &Rectangle {
@@ -141,6 +239,59 @@ impl<T: Clone> QuadTreeNode<T> {
         }
     }
 
+    /// Returns the bounds and data of the leaf containing `point`, or None if the point lies
+    /// outside this node's bounds.
+    pub fn find_leaf(&self, point: Vector2) -> Option<(&Rectangle, &T)> {
+        if !self.bounds().contains(point) {
+            return None;
+        }
+        let mut node = self;
+        loop {
+            match node {
+                Self::Internal { children, .. } => {
+                    node = children
+                        .iter()
+                        .find(|child| child.bounds().contains(point))?;
+                }
+                Self::Leaf { bounds, data } => return Some((bounds, data)),
+            }
+        }
+    }
+
+    /// Appends the bounds and data of every leaf that overlaps `area` to `out`. Leaves that only
+    /// touch the area along an edge are skipped.
+    pub fn find_leaves<'a>(&'a self, area: &Rectangle, out: &mut Vec<(&'a Rectangle, &'a T)>) {
+        if self.bounds().intersect(*area).is_empty() {
+            return;
+        }
+        match self {
+            Self::Internal { children, .. } => {
+                for child in children.iter() {
+                    child.find_leaves(area, out);
+                }
+            }
+            Self::Leaf { bounds, data } => out.push((bounds, data)),
+        }
+    }
+
+    /// Returns the leaf node containing `point`, or None if the point lies outside this node's
+    /// bounds.
+    pub fn find_leaf_node_mut(&mut self, point: Vector2) -> Option<&mut Self> {
+        if !self.bounds().contains(point) {
+            return None;
+        }
+        if matches!(self, Self::Leaf { .. }) {
+            return Some(self);
+        }
+        match self {
+            Self::Internal { children, .. } => children
+                .iter_mut()
+                .find(|child| child.bounds().contains(point))?
+                .find_leaf_node_mut(point),
+            Self::Leaf { .. } => unreachable!(),
+        }
+    }
+
     /// Returns a reference to the data if this is a leaf node, or None if it's an internal node.
     #[inline]
     pub fn data(&self) -> Option<&T> {
@@ -453,6 +604,7 @@ impl<'a, T: Clone, const CAPACITY: usize> Iterator for QuadTreeLeafIterMut<'a, T
 mod tests {
     use super::*;
     use avian3d::math::Vector2;
+    use std::cell::Cell;
 
     #[test]
     fn test_new_leaf() {
@@ -472,6 +624,62 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_update_splits_and_merges() {
+        let bounds = Rectangle::from_corners(Vector2::ZERO, Vector2::splat(8.0));
+        let mut tree = QuadTreeNode::new(bounds, 0u32);
+
+        let mut diff = QuadTreeDiff::default();
+        let point = Vector2::splat(1.0);
+        let keep = |bounds: &Rectangle| bounds.size().x <= 2.0 || !bounds.contains(point);
+        tree.update(
+            |(bounds, _)| keep(bounds),
+            |(_, _, depth)| depth + 1,
+            keep,
+            |_| 0,
+            &mut diff,
+        );
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.added.len(), 7);
+        assert_eq!(tree.iter().count(), 7);
+
+        // Moving the point merges the south-west quadrant and splits the north-east one.
+        let mut diff = QuadTreeDiff::default();
+        let point = Vector2::splat(7.0);
+        let keep = |bounds: &Rectangle| bounds.size().x <= 2.0 || !bounds.contains(point);
+        let merges = Cell::new(0);
+        tree.update(
+            |(bounds, _)| keep(bounds),
+            |(_, _, depth)| depth + 1,
+            keep,
+            |_| {
+                merges.set(merges.get() + 1);
+                1
+            },
+            &mut diff,
+        );
+        // Merge data is only created for the node that merged.
+        assert_eq!(merges.get(), 1);
+        assert_eq!(diff.removed.len(), 5);
+        assert_eq!(diff.added.len(), 5);
+        assert_eq!(tree.iter().count(), 7);
+        assert_eq!(
+            tree.find_leaf(Vector2::splat(1.0)).map(|(_, depth)| *depth),
+            Some(1)
+        );
+        assert_eq!(
+            tree.find_leaf(Vector2::splat(7.0)).map(|(_, depth)| *depth),
+            Some(2)
+        );
+
+        let mut leaves = Vec::new();
+        tree.find_leaves(
+            &Rectangle::from_corners(Vector2::splat(4.0), Vector2::splat(8.0)),
+            &mut leaves,
+        );
+        assert_eq!(leaves.len(), 4);
+    }
+
     // #[test]
     // fn test_bounds() {
     //     let bounds = Rectangle::from_corners(Vector2::new(0.0, 0.0), Vector2::new(10.0, 10.0));
@@ -0,0 +1,246 @@
+use avian3d::{
+    math::{AdjustPrecision, Scalar, Vector},
+    prelude::*,
+};
+use bevy::{
+    ecs::{entity::EntityHashSet, intern::Interned, schedule::ScheduleLabel},
+    prelude::*,
+};
+use big_space::prelude::{Grid, GridCell};
+
+use crate::constants::physics::SEAWATER_DENSITY_KG_M3;
+use crate::plugins::terrain::Body;
+use crate::Precision;
+
+/// Computes the [`Submersion`] of [`Buoyancy`] bodies at the start of every physics step.
+pub struct BuoyancyPlugin {
+    schedule: Interned<dyn ScheduleLabel>,
+}
+
+impl BuoyancyPlugin {
+    /// Creates a [`BuoyancyPlugin`] for the schedule that runs the physics.
+    pub fn new(schedule: impl ScheduleLabel) -> Self {
+        Self {
+            schedule: schedule.intern(),
+        }
+    }
+}
+
+impl Default for BuoyancyPlugin {
+    fn default() -> Self {
+        Self::new(FixedPostUpdate)
+    }
+}
+
+impl Plugin for BuoyancyPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Buoyancy>().add_systems(
+            self.schedule,
+            compute_submersions.in_set(PhysicsSet::Prepare),
+        );
+    }
+}
+
+/// Makes a rigid body float and slow down in the ocean of the [`Body`] it descends from.
+///
+/// The body is treated as `volume` cubic meters of displacement spread evenly over `height`
+/// meters around its position, so it is fully submerged once its position lies `height / 2`
+/// below sea level. The buoyant force pushes against the local gravity at the body's
+/// `center_of_buoyancy`, which rights bodies whose center of buoyancy lies above their
+/// [`ComputedCenterOfMass`], like boats.
+#[derive(Component, Reflect, Copy, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+#[require(Submersion)]
+pub struct Buoyancy {
+    /// Cubic meters of water displaced when fully submerged.
+    pub volume: Scalar,
+    /// Vertical extent of the body, in meters.
+    pub height: Scalar,
+    /// Local offset of the point the buoyant force acts at, from the body's origin.
+    pub center_of_buoyancy: Vector,
+    /// Linear damping, per second, while fully submerged.
+    pub linear_drag: Scalar,
+    /// Angular damping, per second, while fully submerged.
+    pub angular_drag: Scalar,
+}
+
+impl Buoyancy {
+    pub fn new(volume: Scalar, height: Scalar) -> Self {
+        Self {
+            volume,
+            height,
+            center_of_buoyancy: Vector::ZERO,
+            linear_drag: 1.0,
+            angular_drag: 1.0,
+        }
+    }
+
+    pub fn with_center_of_buoyancy(mut self, center_of_buoyancy: Vector) -> Self {
+        self.center_of_buoyancy = center_of_buoyancy;
+        self
+    }
+
+    pub fn with_drag(mut self, linear_drag: Scalar, angular_drag: Scalar) -> Self {
+        self.linear_drag = linear_drag;
+        self.angular_drag = angular_drag;
+        self
+    }
+
+    /// Returns the share of the body below the surface, with its position `depth` meters below
+    /// sea level.
+    pub fn submerged_fraction(&self, depth: Scalar) -> Scalar {
+        if self.height <= 0.0 {
+            return if depth > 0.0 { 1.0 } else { 0.0 };
+        }
+        (depth / self.height + 0.5).clamp(0.0, 1.0)
+    }
+
+    /// Returns the buoyant force on the body in `submersion`, where the local `gravity` pulls on
+    /// it, it is turned by `rotation`, and its local `center_of_mass` is the point it turns about.
+    pub fn force(
+        &self,
+        submersion: &Submersion,
+        gravity: Vector,
+        rotation: &Rotation,
+        center_of_mass: Vector,
+    ) -> BuoyantForce {
+        let submerged = self.submerged_fraction(submersion.depth);
+        let displaced = self.volume * submerged;
+        if displaced == 0.0 {
+            return BuoyantForce::default();
+        }
+        // Archimedes: the water pushes up with the weight of the water displaced.
+        let force = -gravity * submersion.density * displaced;
+        let lever = rotation.0 * (self.center_of_buoyancy - center_of_mass);
+        BuoyantForce {
+            force,
+            torque: lever.cross(force),
+            submerged,
+        }
+    }
+}
+
+/// The buoyant force and torque on a [`Buoyancy`] body, see [`Buoyancy::force`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct BuoyantForce {
+    pub force: Vector,
+    /// Torque about the body's center of mass.
+    pub torque: Vector,
+    /// The share of the body below the surface, see [`Buoyancy::submerged_fraction`].
+    pub submerged: Scalar,
+}
+
+/// How deep a [`Buoyancy`] body lies in the ocean of the [`Body`] it descends from, computed
+/// before each physics step. Bodies outside of any [`Body`] are [`Submersion::DRY`].
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+pub struct Submersion {
+    /// Meters the body's position lies below sea level, negative above it.
+    pub depth: Scalar,
+    /// Density of the water, in kilograms per cubic meter.
+    pub density: Scalar,
+}
+
+impl Submersion {
+    /// Out of any water.
+    pub const DRY: Self = Self {
+        depth: Scalar::NEG_INFINITY,
+        density: 0.0,
+    };
+}
+
+impl Default for Submersion {
+    fn default() -> Self {
+        Self::DRY
+    }
+}
+
+type HierarchyQuery<'w, 's> = Query<'w, 's, (Has<Body>, Option<&'static Children>)>;
+
+/// Computes the submersion of every descendant of a [`Body`], from the position that the
+/// previous physics step synced to its [`Transform`].
+fn compute_submersions(
+    body_query: Query<(&Body, &Grid<Precision>, &Children)>,
+    hierarchy_query: HierarchyQuery,
+    mut submersion_query: Query<(Entity, &GridCell<Precision>, &Transform, &mut Submersion)>,
+    mut visited: Local<EntityHashSet>,
+) {
+    visited.clear();
+    for (body, grid, children) in &body_query {
+        let mut descendants = children.to_vec();
+        while let Some(entity) = descendants.pop() {
+            let Ok((is_body, children)) = hierarchy_query.get(entity) else {
+                continue;
+            };
+            // Bodies nested in this one float in their own oceans.
+            if is_body {
+                continue;
+            }
+            if let Ok((_, cell, transform, mut submersion)) = submersion_query.get_mut(entity) {
+                // Cells of the body's descendants are in its grid, which is centered on it.
+                let position = grid
+                    .grid_position_double(cell, transform)
+                    .adjust_precision();
+                submersion.set_if_neq(match body.ocean_depth(position) {
+                    Some(depth) => Submersion {
+                        depth,
+                        density: SEAWATER_DENSITY_KG_M3,
+                    },
+                    None => Submersion::DRY,
+                });
+                visited.insert(entity);
+            }
+            descendants.extend(
+                children
+                    .into_iter()
+                    .flat_map(|children| children.iter().copied()),
+            );
+        }
+    }
+
+    // Bodies that left their `Body` are out of the water.
+    for (entity, _, _, mut submersion) in &mut submersion_query {
+        if !visited.contains(&entity) {
+            submersion.set_if_neq(Submersion::DRY);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use avian3d::math::Quaternion;
+
+    #[test]
+    fn test_buoyant_force_follows_displaced_water() {
+        let gravity = Vector::NEG_Y * 9.81;
+        let buoyancy = Buoyancy::new(2.0, 1.0);
+        let submersion = |depth| Submersion {
+            depth,
+            density: SEAWATER_DENSITY_KG_M3,
+        };
+        let weight = 2.0 * SEAWATER_DENSITY_KG_M3 * 9.81;
+
+        let upright = Rotation::default();
+        let submerged = buoyancy.force(&submersion(5.0), gravity, &upright, Vector::ZERO);
+        assert!((submerged.force.y - weight).abs() < 1e-6);
+        assert_eq!(submerged.torque, Vector::ZERO);
+        assert_eq!(submerged.submerged, 1.0);
+        // Half submerged, half the water is displaced.
+        let half = buoyancy.force(&submersion(0.0), gravity, &upright, Vector::ZERO);
+        assert!((half.force.y - weight / 2.0).abs() < 1e-6);
+        assert_eq!(half.submerged, 0.5);
+        let dry = buoyancy.force(&Submersion::DRY, gravity, &upright, Vector::ZERO);
+        assert_eq!(dry, BuoyantForce::default());
+
+        // A center of buoyancy above the center of mass turns a tilted body upright.
+        let tilted = Rotation(Quaternion::from_rotation_z(0.3));
+        let boat = buoyancy.with_center_of_buoyancy(Vector::Y);
+        let righting = boat.force(&submersion(5.0), gravity, &tilted, Vector::ZERO);
+        assert!(righting.torque.z < 0.0);
+        // The lever arm is measured from the center of mass, not the body's origin.
+        let balanced = boat.force(&submersion(5.0), gravity, &tilted, Vector::Y);
+        assert!(balanced.torque.length() < 1e-6);
+        let capsizing = boat.force(&submersion(5.0), gravity, &tilted, Vector::Y * 2.0);
+        assert!(capsizing.torque.z > 0.0);
+    }
+}
@@ -10,7 +10,7 @@ use bevy::{
 };
 
 use super::helpers::integrate_velocity;
-use crate::plugins::physics::{GlobalGravity, LocalGravity};
+use crate::plugins::physics::{Buoyancy, GlobalGravity, LocalGravity, Submersion};
 
 #[derive(QueryData)]
 #[query_data(mutable)]
@@ -24,6 +24,7 @@ pub struct VelocityIntegrationQuery {
     force: &'static ExternalForce,
     torque: &'static ExternalTorque,
     mass: &'static ComputedMass,
+    center_of_mass: &'static ComputedCenterOfMass,
     angular_inertia: &'static ComputedAngularInertia,
     global_angular_inertia: &'static GlobalAngularInertia,
     lin_damping: Option<&'static LinearDamping>,
@@ -33,6 +34,8 @@ pub struct VelocityIntegrationQuery {
     local_gravity: Option<&'static LocalGravity>,
     gravity_scale: Option<&'static GravityScale>,
     locked_axes: Option<&'static LockedAxes>,
+    buoyancy: Option<&'static Buoyancy>,
+    submersion: Option<&'static Submersion>,
 }
 
 pub type RigidBodyActiveFilter = (Without<RigidBodyDisabled>, Without<Sleeping>);
@@ -77,11 +80,23 @@ pub fn integrate_velocities(
                 }
             }
 
-            let external_force = body.force.force();
-            let external_torque = body.torque.torque() + body.force.torque();
+            let mut external_force = body.force.force();
+            let mut external_torque = body.torque.torque() + body.force.torque();
             let gravity = body.local_gravity.map_or(global_gravity.0, |local| local.0)
                 * body.gravity_scale.map_or(1.0, |scale| scale.0);
 
+            // Apply buoyancy and water drag
+            if let (Some(buoyancy), Some(submersion)) = (body.buoyancy, body.submersion) {
+                let buoyant = buoyancy.force(submersion, gravity, body.rot, body.center_of_mass.0);
+                if buoyant.submerged > 0.0 {
+                    let submerged = buoyant.submerged;
+                    external_force += buoyant.force;
+                    external_torque += buoyant.torque;
+                    body.lin_vel.0 *= 1.0 / (1.0 + delta_secs * buoyancy.linear_drag * submerged);
+                    body.ang_vel.0 *= 1.0 / (1.0 + delta_secs * buoyancy.angular_drag * submerged);
+                }
+            }
+
             integrate_velocity(
                 &mut body.lin_vel.0,
                 &mut body.ang_vel.0,
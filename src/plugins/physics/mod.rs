@@ -4,13 +4,16 @@ use bevy::{
     prelude::*,
 };
 
+pub mod buoyancy;
 pub mod character_controller;
 pub mod gravity;
 mod integrator;
 
+pub use buoyancy::{Buoyancy, Submersion};
 pub use character_controller::CharacterController;
 pub use gravity::{GlobalGravity, GravityField, LocalGravity};
 
+use buoyancy::BuoyancyPlugin;
 use character_controller::CharacterControllerPlugin;
 use gravity::GravityPlugin;
 use integrator::CustomIntegratorPlugin;
@@ -50,6 +53,7 @@ impl Plugin for PhysicsPlugin {
                 .add_after::<PhysicsSchedulePlugin>(CustomIntegratorPlugin::default()),
         )
         .add_plugins(GravityPlugin)
+        .add_plugins(BuoyancyPlugin::new(self.schedule))
         .add_plugins(CharacterControllerPlugin)
         .insert_resource(Time::from_hz(144.0));
     }
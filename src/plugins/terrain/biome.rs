@@ -0,0 +1,304 @@
+use avian3d::math::{Scalar, Vector, PI};
+use bevy::prelude::*;
+use std::hash::Hasher;
+use std::ops::Range;
+
+use super::disk_cache::FingerprintHasher;
+use super::height::{FractalNoise, HeightSource};
+
+/// How many biomes a body can have, one per component of
+/// [`ATTRIBUTE_BIOME_WEIGHTS`](super::mesh::ATTRIBUTE_BIOME_WEIGHTS).
+pub const MAX_BIOMES: usize = 4;
+
+/// The conditions at a point on a body's surface that decide which biome it belongs to.
+#[derive(Reflect, Copy, Clone, Debug, PartialEq)]
+pub struct Climate {
+    /// Degrees Celsius.
+    pub temperature: Scalar,
+    /// From `0.0` for the driest to `1.0` for the wettest places.
+    pub moisture: Scalar,
+    /// Meters above the body's sea level.
+    pub altitude: Scalar,
+    /// Angle between the surface normal and the vertical, in radians.
+    pub slope: Scalar,
+}
+
+/// Derives the [`Climate`] of a body's surface.
+///
+/// Temperature falls from the equator toward the poles of the body's y axis, and with altitude.
+/// Moisture is noise over the unit sphere, so wet and dry regions span the same share of every
+/// body regardless of its size.
+#[derive(Reflect, Copy, Clone, Debug, PartialEq)]
+pub struct ClimateModel {
+    /// Temperature at zero altitude on the equator, in degrees Celsius.
+    pub equator_temperature: Scalar,
+    /// Temperature at zero altitude on the poles, in degrees Celsius.
+    pub pole_temperature: Scalar,
+    /// Temperature drop per meter of altitude, in degrees Celsius.
+    pub lapse_rate: Scalar,
+    /// Moisture noise, whose output is mapped from `[-amplitude, amplitude]` to `[0, 1]`.
+    pub moisture: FractalNoise,
+}
+
+impl ClimateModel {
+    pub const EARTH: Self = Self {
+        equator_temperature: 30.0,
+        pole_temperature: -25.0,
+        lapse_rate: 0.0065,
+        moisture: FractalNoise {
+            seed: 0x5EED_0101,
+            octaves: 6,
+            frequency: 1.5,
+            amplitude: 1.0,
+            lacunarity: 2.0,
+            persistence: 0.5,
+        },
+    };
+
+    /// Returns the climate at the unit-sphere `direction`, where the surface lies `altitude`
+    /// meters above the body's sea level and has the given `normal`.
+    pub fn climate(&self, direction: Vector, altitude: Scalar, normal: Vector) -> Climate {
+        let latitude = direction.y.abs();
+        let temperature = self.equator_temperature
+            + (self.pole_temperature - self.equator_temperature) * latitude
+            - self.lapse_rate * altitude.max(0.0);
+        let moisture = if self.moisture.amplitude == 0.0 {
+            0.5
+        } else {
+            (self.moisture.height(direction) / self.moisture.amplitude * 0.5 + 0.5).clamp(0.0, 1.0)
+        };
+        Climate {
+            temperature,
+            moisture,
+            altitude,
+            slope: normal.dot(direction).clamp(-1.0, 1.0).acos(),
+        }
+    }
+}
+
+impl Default for ClimateModel {
+    fn default() -> Self {
+        Self::EARTH
+    }
+}
+
+/// A kind of terrain, which a point belongs to while its [`Climate`] lies within every range.
+#[derive(Reflect, Clone, Debug, PartialEq)]
+pub struct Biome {
+    pub name: &'static str,
+    pub temperature: Range<Scalar>,
+    pub moisture: Range<Scalar>,
+    pub altitude: Range<Scalar>,
+    pub slope: Range<Scalar>,
+    /// The color the terrain material shades the biome with.
+    pub color: Color,
+}
+
+impl Biome {
+    /// A biome that every climate lies within.
+    pub fn new(name: &'static str, color: Color) -> Self {
+        Self {
+            name,
+            temperature: Scalar::NEG_INFINITY..Scalar::INFINITY,
+            moisture: Scalar::NEG_INFINITY..Scalar::INFINITY,
+            altitude: Scalar::NEG_INFINITY..Scalar::INFINITY,
+            slope: Scalar::NEG_INFINITY..Scalar::INFINITY,
+            color,
+        }
+    }
+
+    pub fn with_temperature(mut self, temperature: Range<Scalar>) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    pub fn with_moisture(mut self, moisture: Range<Scalar>) -> Self {
+        self.moisture = moisture;
+        self
+    }
+
+    pub fn with_altitude(mut self, altitude: Range<Scalar>) -> Self {
+        self.altitude = altitude;
+        self
+    }
+
+    pub fn with_slope(mut self, slope: Range<Scalar>) -> Self {
+        self.slope = slope;
+        self
+    }
+
+    /// Returns how far `climate` lies outside the biome, measuring each condition in multiples
+    /// of its `transition` width. Climates within every range are at zero distance.
+    pub fn distance(&self, climate: &Climate, transition: &Climate) -> Scalar {
+        [
+            (
+                &self.temperature,
+                climate.temperature,
+                transition.temperature,
+            ),
+            (&self.moisture, climate.moisture, transition.moisture),
+            (&self.altitude, climate.altitude, transition.altitude),
+            (&self.slope, climate.slope, transition.slope),
+        ]
+        .into_iter()
+        .map(|(range, value, width)| {
+            let outside = (range.start - value).max(value - range.end).max(0.0);
+            (outside / width.max(Scalar::EPSILON)).powi(2)
+        })
+        .sum::<Scalar>()
+        .sqrt()
+    }
+}
+
+/// The biomes of a [`Body`](super::Body) and the climate they are classified by.
+///
+/// Chunk meshes store how much each vertex belongs to each biome in
+/// [`ATTRIBUTE_BIOME_WEIGHTS`](super::mesh::ATTRIBUTE_BIOME_WEIGHTS), in the order of
+/// `biomes`, and the terrain material blends the biome colors by those weights. Only the first
+/// [`MAX_BIOMES`] biomes are used.
+#[derive(Component, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct Biomes {
+    pub climate: ClimateModel,
+    pub biomes: Vec<Biome>,
+    /// How far each condition may lie outside a biome's range while the biome still blends in.
+    pub transition: Climate,
+}
+
+impl Biomes {
+    pub fn new(climate: ClimateModel, biomes: Vec<Biome>) -> Self {
+        debug_assert!(
+            biomes.len() <= MAX_BIOMES,
+            "expected at most {MAX_BIOMES} biomes"
+        );
+        Self {
+            climate,
+            biomes,
+            transition: Climate {
+                temperature: 5.0,
+                moisture: 0.1,
+                altitude: 200.0,
+                slope: 0.1,
+            },
+        }
+    }
+
+    pub fn with_transition(mut self, transition: Climate) -> Self {
+        self.transition = transition;
+        self
+    }
+
+    /// Grassland, desert, rock on steep slopes, and snow where it is freezing.
+    pub fn earth() -> Self {
+        Self::new(
+            ClimateModel::EARTH,
+            vec![
+                Biome::new("Grassland", Color::linear_rgb(0.12, 0.64, 0.14))
+                    .with_temperature(0.0..35.0)
+                    .with_moisture(0.4..1.0)
+                    .with_slope(0.0..PI / 6.0),
+                Biome::new("Desert", Color::linear_rgb(0.76, 0.62, 0.38))
+                    .with_temperature(10.0..50.0)
+                    .with_moisture(0.0..0.4)
+                    .with_slope(0.0..PI / 6.0),
+                Biome::new("Rock", Color::linear_rgb(0.32, 0.3, 0.28)).with_slope(PI / 6.0..PI),
+                Biome::new("Snow", Color::linear_rgb(0.9, 0.92, 0.95))
+                    .with_temperature(Scalar::NEG_INFINITY..0.0)
+                    .with_slope(0.0..PI / 4.0),
+            ],
+        )
+    }
+
+    /// Returns how much a point with the given `climate` belongs to each biome, summing to one.
+    ///
+    /// Points blend between the biomes they are closest to, and belong almost wholly to a biome
+    /// once every other one is a few [`Self::transition`] widths further away.
+    pub fn weights(&self, climate: &Climate) -> [f32; MAX_BIOMES] {
+        let mut distances = [Scalar::INFINITY; MAX_BIOMES];
+        for (distance, biome) in distances.iter_mut().zip(&self.biomes) {
+            *distance = biome.distance(climate, &self.transition);
+        }
+        let nearest = distances
+            .iter()
+            .copied()
+            .fold(Scalar::INFINITY, Scalar::min);
+        if nearest.is_infinite() {
+            return [0.0; MAX_BIOMES];
+        }
+
+        let weights = distances.map(|distance| (-(distance - nearest).powi(2)).exp());
+        let total: Scalar = weights.iter().sum();
+        weights.map(|weight| (weight / total) as f32)
+    }
+
+    /// Returns the biome colors in the order of their weights, for the terrain material.
+    pub fn colors(&self) -> [LinearRgba; MAX_BIOMES] {
+        std::array::from_fn(|index| {
+            self.biomes
+                .get(index)
+                .map_or(LinearRgba::BLACK, |biome| biome.color.to_linear())
+        })
+    }
+
+    /// Returns a value that changes whenever the weights of a climate change.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = FingerprintHasher::default();
+        hasher.write_u64(self.climate.moisture.fingerprint().unwrap_or_default());
+        let mut write = |value: Scalar| hasher.write_u64(value.to_bits() as u64);
+        write(self.climate.equator_temperature);
+        write(self.climate.pole_temperature);
+        write(self.climate.lapse_rate);
+        for width in [
+            self.transition.temperature,
+            self.transition.moisture,
+            self.transition.altitude,
+            self.transition.slope,
+        ] {
+            write(width);
+        }
+        for biome in self.biomes.iter().take(MAX_BIOMES) {
+            for range in [
+                &biome.temperature,
+                &biome.moisture,
+                &biome.altitude,
+                &biome.slope,
+            ] {
+                write(range.start);
+                write(range.end);
+            }
+        }
+        hasher.finish()
+    }
+}
+
+impl Default for Biomes {
+    fn default() -> Self {
+        Self::earth()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weights_pick_the_biome_the_climate_lies_in() {
+        let biomes = Biomes::earth();
+        let climate = |temperature, moisture, slope| Climate {
+            temperature,
+            moisture,
+            altitude: 0.0,
+            slope,
+        };
+
+        let grassland = biomes.weights(&climate(20.0, 0.8, 0.0));
+        assert!(grassland[0] > 0.99, "{grassland:?}");
+        let rock = biomes.weights(&climate(20.0, 0.8, PI / 3.0));
+        assert!(rock[2] > 0.99, "{rock:?}");
+
+        // Between grassland and desert, both contribute.
+        let edge = biomes.weights(&climate(20.0, 0.4, 0.0));
+        assert!(edge[0] > 0.2 && edge[1] > 0.2, "{edge:?}");
+        assert!((edge.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+    }
+}
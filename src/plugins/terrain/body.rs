@@ -1,7 +1,14 @@
 use super::{
-    cube_tree::{Axis, CubeTree},
-    material::{TerrainMaterial, TerrainMaterials},
-    GenerateMeshes,
+    biome::Biomes,
+    cube_tree::{Axis, CubeTree, LodSettings},
+    height::{FractalNoise, Heightfield},
+    material::{
+        CompactChunk, CompactChunkRecord, CompactChunkSlots, TerrainCompactMaterial,
+        TerrainMaterial, TerrainMaterials, TerrainStandardMaterial,
+    },
+    mesh::VertexMode,
+    ocean::OceanMaterial,
+    GenerateMeshes, TerrainPluginConfig,
 };
 use crate::plugins::terrain::cube_tree::ChunkHash;
 use crate::{
@@ -13,6 +20,7 @@ use avian3d::math::{Scalar, Vector};
 use bevy::{
     ecs::{component::ComponentId, world::DeferredWorld},
     prelude::*,
+    render::storage::ShaderStorageBuffer,
     utils::HashMap,
 };
 use bevy_inspector_egui::inspector_options::{InspectorOptions, ReflectInspectorOptions};
@@ -23,6 +31,8 @@ use std::ops::{Deref, DerefMut};
 pub struct BodyPreset {
     pub mass: Scalar,
     pub radius: Scalar,
+    pub terrain: FractalNoise,
+    pub sea_level: Option<Scalar>,
     pub name: Option<&'static str>,
 }
 
@@ -30,12 +40,16 @@ impl BodyPreset {
     pub const EARTH: Self = Self {
         mass: EARTH_MASS_KG,
         radius: EARTH_DIAMETER_M / 2.0,
+        terrain: FractalNoise::EARTH,
+        sea_level: None,
         name: Some("Earth"),
     };
 
     pub const MOON: Self = Self {
         mass: MOON_MASS_KG,
         radius: MOON_DIAMETER_M / 2.0,
+        terrain: FractalNoise::MOON,
+        sea_level: None,
         name: Some("Moon"),
     };
 }
@@ -47,6 +61,8 @@ impl std::ops::Div<Scalar> for BodyPreset {
         let mut res = self;
         res.mass /= rhs.powi(3);
         res.radius /= rhs;
+        res.terrain.amplitude /= rhs;
+        res.sea_level = res.sea_level.map(|sea_level| sea_level / rhs);
         res
     }
 }
@@ -58,6 +74,13 @@ impl std::ops::Div<Scalar> for BodyPreset {
 pub struct Body {
     pub mass: Scalar,
     pub radius: Scalar,
+    /// Elevation noise used when no custom [`Heightfield`] is attached to the body.
+    pub terrain: FractalNoise,
+    /// Elevation of the ocean surface above `radius`, in meters, or `None` for bodies without an
+    /// ocean. Terrain altitudes, and with them biomes, are measured from sea level.
+    ///
+    /// Presets come without an ocean; add one with [`Body::with_sea_level`].
+    pub sea_level: Option<Scalar>,
     pub name: Option<&'static str>,
 }
 
@@ -67,6 +90,8 @@ impl Body {
         Self {
             mass,
             radius,
+            terrain: FractalNoise::FLAT,
+            sea_level: None,
             name: None,
         }
     }
@@ -75,16 +100,40 @@ impl Body {
         Self {
             mass: preset.mass,
             radius: preset.radius,
+            terrain: preset.terrain,
+            sea_level: preset.sea_level,
             name: preset.name,
         }
     }
 
+    pub fn with_terrain(mut self, terrain: FractalNoise) -> Self {
+        self.terrain = terrain;
+        self
+    }
+
+    pub fn with_sea_level(mut self, sea_level: Scalar) -> Self {
+        self.sea_level = Some(sea_level);
+        self
+    }
+
+    /// Returns the distance from the body's center to its ocean surface.
+    pub fn sea_radius(&self) -> Option<Scalar> {
+        self.sea_level.map(|sea_level| self.radius + sea_level)
+    }
+
+    /// Returns how far below the ocean surface `position`, relative to the body's center, lies.
+    /// Positions above the surface have a negative depth.
+    pub fn ocean_depth(&self, position: Vector) -> Option<Scalar> {
+        self.sea_radius()
+            .map(|sea_radius| sea_radius - position.length())
+    }
+
     fn name(&self) -> Name {
         self.name.map_or(Name::new("Body"), Name::new)
     }
 }
 fn on_add_body(mut world: DeferredWorld, entity: Entity, id: ComponentId) {
-    let material_handle = world
+    let mut material_handle = world
         .get_resource::<TerrainMaterials>()
         .expect("expected TerrainMaterials resource to exist")
         .standard
@@ -92,22 +141,94 @@ fn on_add_body(mut world: DeferredWorld, entity: Entity, id: ComponentId) {
 
     debug_assert!(world.get_by_id(entity, id).is_some());
 
+    // Bodies with their own biomes and no material of their own get a copy of the standard
+    // material with their biome colors.
+    let biomes = world.get::<Biomes>(entity).cloned().unwrap_or_default();
+    if biomes != Biomes::default() && world.get::<TerrainMaterial>(entity).is_none() {
+        let mut materials = world.resource_mut::<Assets<TerrainStandardMaterial>>();
+        if let Some(material) = materials.get(&material_handle) {
+            let material = TerrainStandardMaterial {
+                base: material.base.clone(),
+                extension: material.extension.clone().with_biomes(&biomes),
+            };
+            material_handle = materials.add(material);
+        }
+    }
+    let terrain_material = world
+        .get::<TerrainMaterial>(entity)
+        .cloned()
+        .unwrap_or(TerrainMaterial::Standard(material_handle));
+
     let body = unsafe {
         *world
             .get_by_id(entity, id)
             .unwrap_unchecked()
             .deref::<Body>()
     };
+    let heightfield = world
+        .get::<Heightfield>(entity)
+        .cloned()
+        .unwrap_or_else(|| Heightfield::from(body.terrain));
+    let config = world.get_resource::<TerrainPluginConfig>().copied();
+    let balanced = config.is_none_or(|config| config.balanced);
+    let lod_settings = world
+        .get::<LodSettings>(entity)
+        .copied()
+        .unwrap_or_else(|| {
+            let mut settings = LodSettings::for_radius(body.radius);
+            if let Some(config) = config {
+                settings.subdivisions = config.subdivisions;
+            }
+            settings
+        });
+    if body.sea_level.is_some() && world.get::<OceanMaterial>(entity).is_none() {
+        let ocean_material = world.resource::<TerrainMaterials>().ocean.clone();
+        world
+            .commands()
+            .entity(entity)
+            .insert(OceanMaterial(ocean_material));
+    }
+    let cube_tree = CubeTree::new(body.radius, heightfield.clone())
+        .with_balancing(balanced)
+        .with_settings(lod_settings);
+    // Compact chunks of the body share one material, which holds where each of them lies.
+    if config.is_some_and(|config| config.vertex_mode == VertexMode::Compact) {
+        let records = vec![CompactChunkRecord::default()];
+        let chunks = world
+            .resource_mut::<Assets<ShaderStorageBuffer>>()
+            .add(ShaderStorageBuffer::from(records));
+        let base = terrain_material
+            .standard()
+            .and_then(|handle| {
+                world
+                    .resource::<Assets<TerrainStandardMaterial>>()
+                    .get(handle)
+            })
+            .map(|material| material.base.clone())
+            .unwrap_or_default();
+        let extension = CompactChunk::new(chunks).with_biomes(&biomes);
+        let material = world
+            .resource_mut::<Assets<TerrainCompactMaterial>>()
+            .add(TerrainCompactMaterial { base, extension });
+        world
+            .commands()
+            .entity(entity)
+            .insert(CompactChunkSlots::new(material));
+    }
+
     #[cfg(debug_assertions)]
     world
         .commands()
         .entity(entity)
         .insert((
             body.name(),
-            TerrainMaterial::Standard(material_handle),
-            CubeTree::new(body.radius),
+            terrain_material,
+            cube_tree,
+            lod_settings,
             GravityField::radial_from_mass(body.mass),
             Radius(body.radius),
+            heightfield,
+            biomes,
         ))
         .trigger(GenerateMeshes(Vector::MAX));
 
@@ -115,7 +236,13 @@ fn on_add_body(mut world: DeferredWorld, entity: Entity, id: ComponentId) {
     world
         .commands()
         .entity(entity)
-        .insert(TerrainMaterial(material_handle))
+        .insert((
+            terrain_material,
+            cube_tree,
+            lod_settings,
+            heightfield,
+            biomes,
+        ))
         .trigger(crate::plugins::terrain::GenerateMeshes(Vector::MAX));
 }
 
@@ -161,36 +288,11 @@ impl Chunk {
                 .clone()
         };
 
-        #[cfg(debug_assertions)]
-        match material {
-            TerrainMaterial::DebugNormals(handle) => {
-                world
-                    .commands()
-                    .entity(entity)
-                    .insert(MeshMaterial3d(handle.clone()));
-            }
-            TerrainMaterial::DebugUVs(handle) => {
-                world
-                    .commands()
-                    .entity(entity)
-                    .insert(MeshMaterial3d(handle.clone()));
-            }
-            TerrainMaterial::Standard(handle) => {
-                world
-                    .commands()
-                    .entity(entity)
-                    .insert(MeshMaterial3d(handle.clone()));
-            }
-        };
-
-        #[cfg(not(debug_assertions))]
-        world
-            .commands()
-            .entity(entity)
-            .insert(MeshMaterial3d(material.handle()));
+        material.apply(&mut world.commands().entity(entity));
     }
 }
 
+/// Spawned chunk entities of a body, keyed by the hash of the leaf they were built for.
 #[derive(Component)]
 pub struct ChunkCache(HashMap<ChunkHash, Entity>);
 
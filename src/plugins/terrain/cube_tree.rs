@@ -1,13 +1,20 @@
 use avian3d::math::{Scalar, Vector, Vector2};
+use bevy::math::{I64Vec2, U64Vec2};
 use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use bevy_inspector_egui::inspector_options::{InspectorOptions, ReflectInspectorOptions};
 use std::ops::{Index, IndexMut};
 
-use crate::math::quad_tree::{QuadTreeLeafIterMut, Quadrant};
+use crate::constants::terrain::CHUNK_SUBDIVISIONS;
+use crate::math::quad_tree::{QuadTreeDiff, QuadTreeLeafIterMut, Quadrant};
 use crate::math::{
     quad_tree::{QuadTreeLeafIter, QuadTreeNode},
     Rectangle,
 };
-use crate::plugins::terrain::helpers::center_on_sphere;
+use crate::plugins::terrain::height::{HeightSource, Heightfield};
+use crate::plugins::terrain::helpers::{
+    center_on_sphere, cube_to_face, face_to_cube, unit_cube_to_sphere,
+};
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 #[repr(u8)]
@@ -153,40 +160,100 @@ impl std::ops::Mul<f32> for &Axis {
     }
 }
 
+/// One of the four edges of a chunk, in the local frame of its cube face.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[repr(u8)]
+pub enum Edge {
+    North = 0,
+    East = 1,
+    South = 2,
+    West = 3,
+}
+
+impl Edge {
+    pub const ALL: [Self; 4] = [Self::North, Self::East, Self::South, Self::West];
+
+    /// Returns the unit offset from a chunk's center towards this edge, in face coordinates.
+    pub fn offset(&self) -> Vector2 {
+        match self {
+            Edge::North => Vector2::Y,
+            Edge::East => Vector2::X,
+            Edge::South => Vector2::NEG_Y,
+            Edge::West => Vector2::NEG_X,
+        }
+    }
+}
+
+/// How many levels coarser the neighbour across each [`Edge`] of a chunk is.
+///
+/// Neighbours at the same or a finer depth are recorded as `0`; the finer chunk is responsible
+/// for matching its edge to the coarser one.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct EdgeLods(pub [u8; 4]);
+
+impl EdgeLods {
+    pub const NONE: Self = Self([0; 4]);
+}
+
+impl Index<Edge> for EdgeLods {
+    type Output = u8;
+
+    fn index(&self, index: Edge) -> &Self::Output {
+        &self.0[index as usize]
+    }
+}
+
+impl IndexMut<Edge> for EdgeLods {
+    fn index_mut(&mut self, index: Edge) -> &mut Self::Output {
+        &mut self.0[index as usize]
+    }
+}
+
+/// Identifies a chunk by its cube face, depth, collider flag and quadrant path.
+///
+/// Packed into a `u128`: the axis in bits `0..3`, the depth in bits `3..9`, the collider flag in
+/// bit `9` and up to [`ChunkHash::PATH_CAPACITY`] 3-bit quadrants from bit `10`, the most recently
+/// pushed quadrant first. Chunks are unique up to that depth, which splits the face of an
+/// Earth-sized body into chunks far smaller than a metre.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
-pub struct ChunkHash(u32);
+pub struct ChunkHash(u128);
 
 impl ChunkHash {
-    pub fn new(axis: Axis, depth: u8, collider: bool, path: [Quadrant; 7]) -> Self {
+    /// The number of quadrants the path can hold before the oldest ones are dropped.
+    pub const PATH_CAPACITY: usize = 39;
+
+    const PATH_MASK: u128 = (1 << (Self::PATH_CAPACITY * 3)) - 1;
+
+    /// Creates a hash from a path of at most [`ChunkHash::PATH_CAPACITY`] quadrants, the most
+    /// recently pushed quadrant first.
+    pub fn new(axis: Axis, depth: u8, collider: bool, path: &[Quadrant]) -> Self {
         debug_assert!(depth <= 63, "depth is too large for 6 bits");
-        let mut hash = (axis as u32) & 0b111;
-        hash |= (depth as u32 & 0b111_111) << 3;
-        hash |= (collider as u32) << 9;
+        debug_assert!(path.len() <= Self::PATH_CAPACITY, "path is too long");
+        let mut hash = (axis as u128) & 0b111;
+        hash |= (depth as u128 & 0b111_111) << 3;
+        hash |= (collider as u128) << 9;
         for (i, &quadrant) in path.iter().enumerate() {
             let shift = 10 + (i * 3);
-            hash |= (quadrant as u32 & 0b111) << shift;
+            hash |= (quadrant as u128 & 0b111) << shift;
         }
         Self(hash)
     }
     #[inline]
     pub fn new_root(axis: Axis) -> Self {
-        Self::new(axis, 0, false, [Quadrant::ROOT; 7])
+        Self::new(axis, 0, false, &[])
     }
 
     pub fn push_quadrant(&self, new_quadrant: Quadrant) -> Self {
         let header_bits = self.0 & 0x3FF; // 0x3FF = 0b11_1111_1111 (first 10 bits)
-        let path_bits = (self.0 >> 10) & 0x7FFFFF; // 0x7FFFFF = 23 bits for 7 quadrants minus last one
-        let shifted_path = path_bits << 3;
-        let new_path = shifted_path | (new_quadrant as u32 & 0b111);
-        let result = header_bits | (new_path << 10);
-
-        Self(result)
+        let path_bits = self.0 >> 10;
+        let new_path = ((path_bits << 3) | (new_quadrant as u128 & 0b111)) & Self::PATH_MASK;
+        Self(header_bits | (new_path << 10))
     }
 
     #[inline]
     pub fn with_depth(&self, depth: u8) -> Self {
         debug_assert!(depth <= 63, "depth is too large for 6 bits");
-        Self((self.0 & !(0b111_111 << 3)) | ((depth as u32 & 0b111_111) << 3))
+        Self((self.0 & !(0b111_111 << 3)) | ((depth as u128 & 0b111_111) << 3))
     }
 
     #[inline]
@@ -196,12 +263,18 @@ impl ChunkHash {
 
     #[inline]
     pub fn with_collider(&self, collider: bool) -> Self {
-        Self((self.0 & !(0b1 << 9)) | ((collider as u32) << 9))
+        Self((self.0 & !(0b1 << 9)) | ((collider as u128) << 9))
+    }
+
+    /// Returns the packed representation of the hash.
+    #[inline]
+    pub fn to_bits(&self) -> u128 {
+        self.0
     }
 
     #[inline]
     pub fn axis(&self) -> Axis {
-        Axis::from(self.0 & 0b111)
+        Axis::from((self.0 & 0b111) as u32)
     }
 
     #[inline]
@@ -215,60 +288,341 @@ impl ChunkHash {
     }
 
     #[inline]
-    pub fn path(&self) -> [Quadrant; 7] {
-        let mut path = [Quadrant::ROOT; 7];
-        for i in 0..7 {
+    pub fn path(&self) -> [Quadrant; Self::PATH_CAPACITY] {
+        let mut path = [Quadrant::ROOT; Self::PATH_CAPACITY];
+        for (i, quadrant) in path.iter_mut().enumerate() {
             let shift = 10 + (i * 3);
-            path[i] = Quadrant::from((self.0 >> shift) & 0b111);
+            *quadrant = Quadrant::from(((self.0 >> shift) & 0b111) as u32);
         }
         path
     }
 
     #[inline]
-    pub fn values(&self) -> (Axis, u8, [Quadrant; 7], bool) {
+    pub fn values(&self) -> (Axis, u8, [Quadrant; Self::PATH_CAPACITY], bool) {
         (self.axis(), self.depth(), self.path(), self.collider())
     }
+
+    /// Returns the integer cell coordinates of the chunk on its face, with `(0, 0)` in the
+    /// south-west corner and `2^depth` cells along each side.
+    pub fn coordinates(&self) -> U64Vec2 {
+        let depth = self.depth() as usize;
+        self.path()
+            .iter()
+            .take(depth)
+            .rev()
+            .fold(U64Vec2::ZERO, |coordinates, quadrant| {
+                let (x, y) = match quadrant {
+                    Quadrant::SE => (1, 0),
+                    Quadrant::NW => (0, 1),
+                    Quadrant::NE => (1, 1),
+                    _ => (0, 0),
+                };
+                coordinates * 2 + U64Vec2::new(x, y)
+            })
+    }
+
+    /// Builds the hash of the chunk at the given cell coordinates, see [`ChunkHash::coordinates`].
+    pub fn from_coordinates(axis: Axis, depth: u8, collider: bool, coordinates: U64Vec2) -> Self {
+        (0..depth).rev().fold(
+            Self::new_root(axis)
+                .with_depth(depth)
+                .with_collider(collider),
+            |hash, level| {
+                let quadrant = match ((coordinates.x >> level) & 1, (coordinates.y >> level) & 1) {
+                    (0, 0) => Quadrant::SW,
+                    (1, 0) => Quadrant::SE,
+                    (0, _) => Quadrant::NW,
+                    _ => Quadrant::NE,
+                };
+                hash.push_quadrant(quadrant)
+            },
+        )
+    }
+
+    /// Builds the hash of the chunk with the given bounds in the face space of a cube tree with
+    /// the given radius. The collider flag is left unset.
+    pub fn from_bounds(axis: Axis, bounds: &Rectangle, radius: Scalar) -> Self {
+        let size = bounds.size().x;
+        let depth = (2.0 * radius / size).log2().round() as u8;
+        let coordinates = ((bounds.min + radius) / size).round().as_u64vec2();
+        Self::from_coordinates(axis, depth, false, coordinates)
+    }
+
+    /// Returns the bounds of the chunk in the face space of a cube tree with the given radius.
+    pub fn bounds(&self, radius: Scalar) -> Rectangle {
+        let size = 2.0 * radius / (1u64 << self.depth()) as Scalar;
+        let min = Vector2::splat(-radius) + cells_to_vector(self.coordinates().as_i64vec2()) * size;
+        Rectangle::from_corners(min, min + Vector2::splat(size))
+    }
+
+    /// Returns the hash of the chunk at the same depth directly across `edge`, wrapping onto the
+    /// adjacent cube face when the edge lies on a face border.
+    ///
+    /// The neighbour's face may be rotated relative to this one, so walking back across the
+    /// opposite edge does not necessarily return to this chunk.
+    pub fn neighbor(&self, edge: Edge) -> Self {
+        let depth = self.depth();
+        let cells = 1i64 << depth;
+        let coordinates = self.coordinates().as_i64vec2() + edge.offset().as_i64vec2();
+        if coordinates.cmpge(I64Vec2::ZERO).all() && coordinates.cmplt(I64Vec2::splat(cells)).all()
+        {
+            return Self::from_coordinates(
+                self.axis(),
+                depth,
+                self.collider(),
+                coordinates.as_u64vec2(),
+            );
+        }
+
+        // Fold the center of the out-of-bounds cell onto the adjacent face.
+        let face_pos = (cells_to_vector(coordinates) + 0.5) / cells as Scalar * 2.0 - 1.0;
+        let (axis, face_pos) = cube_to_face(face_to_cube(self.axis(), face_pos));
+        let coordinates = ((face_pos + 1.0) / 2.0 * cells as Scalar)
+            .floor()
+            .clamp(Vector2::ZERO, Vector2::splat((cells - 1) as Scalar));
+        Self::from_coordinates(axis, depth, self.collider(), coordinates.as_u64vec2())
+    }
+}
+
+/// Converts integer cell coordinates to a vector of the crate's precision.
+#[inline]
+fn cells_to_vector(cells: I64Vec2) -> Vector2 {
+    #[cfg(feature = "f64")]
+    {
+        cells.as_dvec2()
+    }
+
+    #[cfg(not(feature = "f64"))]
+    {
+        cells.as_vec2()
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
 pub struct ChunkData {
     pub center: Vector,
     pub hash: ChunkHash,
+    pub edge_lods: EdgeLods,
+    /// Conservative bounds on the elevation of the chunk's surface, in meters.
+    pub min_elevation: Scalar,
+    pub max_elevation: Scalar,
+    /// Largest angle, in radians, between the chunk's center and any point on its surface, as
+    /// seen from the center of the body.
+    pub angular_radius: Scalar,
 }
 
 impl ChunkData {
-    pub fn new(axis: Axis, bounds: &Rectangle, radius: Scalar, hash: ChunkHash) -> Self {
+    pub fn new(
+        axis: Axis,
+        bounds: &Rectangle,
+        radius: Scalar,
+        height: &dyn HeightSource,
+        hash: ChunkHash,
+    ) -> Self {
+        let direction = center_on_sphere(axis, radius, bounds) / radius;
+        let center = direction * (radius + height.height(direction));
+
+        // Sample a 3x3 grid over the chunk. Every point of the chunk lies within half the
+        // angular radius of a sample, so widening by that much variation bounds the whole chunk.
+        let mut angular_radius: Scalar = 0.0;
+        let mut min_elevation = Scalar::INFINITY;
+        let mut max_elevation = Scalar::NEG_INFINITY;
+        for y in 0..3 {
+            for x in 0..3 {
+                let t = Vector2::new(x as Scalar, y as Scalar) / 2.0;
+                let face_pos = (bounds.min + bounds.size() * t) / radius;
+                let sample = unit_cube_to_sphere(face_to_cube(axis, face_pos)).normalize();
+                let elevation = height.height(sample);
+                angular_radius = angular_radius.max(direction.angle_between(sample));
+                min_elevation = min_elevation.min(elevation);
+                max_elevation = max_elevation.max(elevation);
+            }
+        }
+        // Sources without a bound are assumed to vary between samples by no more than across
+        // them.
+        let margin = height
+            .max_variation(angular_radius / 2.0)
+            .unwrap_or(max_elevation - min_elevation);
+
         Self {
-            center: center_on_sphere(axis, radius, bounds),
+            center,
             hash,
+            edge_lods: EdgeLods::NONE,
+            min_elevation: min_elevation - margin,
+            max_elevation: max_elevation + margin,
+            angular_radius,
+        }
+    }
+
+    pub fn new_root(
+        axis: Axis,
+        bounds: &Rectangle,
+        radius: Scalar,
+        height: &dyn HeightSource,
+    ) -> Self {
+        Self::new(axis, bounds, radius, height, ChunkHash::new_root(axis))
+    }
+}
+
+pub type CubeTreeNode = QuadTreeNode<ChunkData>;
+
+/// Level of detail tuning for the [`CubeTree`] of a [`Body`](super::Body).
+///
+/// Every body carries one; editing it at runtime rebuilds the body's tree.
+#[derive(Component, Reflect, Copy, Clone, Debug, PartialEq, InspectorOptions)]
+#[reflect(Component, InspectorOptions)]
+pub struct LodSettings {
+    /// Chunks split while an observer is closer than this many times their size.
+    #[inspector(min = 0.0)]
+    pub split_factor: Scalar,
+    /// Chunks of this size or smaller are never split.
+    #[inspector(min = 1.0)]
+    pub min_chunk_size: Scalar,
+    /// Chunks at this depth are never split.
+    // The inspector's maximum is `ChunkHash::PATH_CAPACITY`, which attributes cannot name.
+    #[inspector(min = 1, max = 39)]
+    pub max_depth: u8,
+    /// Chunks at this depth or deeper get colliders.
+    pub collider_depth: u8,
+    /// Vertices between the corners along each side of a chunk mesh.
+    pub subdivisions: usize,
+}
+
+// Keeps the inspector range of `LodSettings::max_depth` in step with the hash.
+const _: () = assert!(ChunkHash::PATH_CAPACITY == 39);
+
+impl LodSettings {
+    /// Returns the default settings for a body of the given radius, with colliders on the chunks
+    /// of the minimum size.
+    pub fn for_radius(radius: Scalar) -> Self {
+        let min_chunk_size = 24.0;
+        Self {
+            split_factor: 1.5,
+            min_chunk_size,
+            max_depth: ChunkHash::PATH_CAPACITY as u8,
+            collider_depth: Self::depth_of_size(radius, min_chunk_size),
+            subdivisions: CHUNK_SUBDIVISIONS,
         }
     }
 
-    pub fn new_root(axis: Axis, bounds: &Rectangle, radius: Scalar) -> Self {
+    /// Returns the settings with [`LodSettings::max_depth`] limited to the depth that
+    /// [`ChunkHash`] can tell chunks apart at.
+    pub fn clamped(mut self) -> Self {
+        self.max_depth = self.max_depth.min(ChunkHash::PATH_CAPACITY as u8);
+        self
+    }
+
+    /// Returns the shallowest depth at which the chunks of a tree with the given radius are no
+    /// larger than `size`.
+    pub fn depth_of_size(radius: Scalar, size: Scalar) -> u8 {
+        (2.0 * radius / size).log2().ceil().max(0.0) as u8
+    }
+}
+
+/// A point of interest the [`CubeTree`] refines around.
+///
+/// Chunks split while the observer is closer than [`LodSettings::split_factor`] times their
+/// size, scaled by `weight` and measured from a sphere of `radius` around `position`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Observer {
+    pub position: Vector,
+    pub weight: Scalar,
+    pub radius: Scalar,
+}
+
+impl Observer {
+    pub fn new(position: Vector) -> Self {
         Self {
-            center: center_on_sphere(axis, radius, bounds),
-            hash: ChunkHash::new_root(axis),
+            position,
+            weight: 1.0,
+            radius: 0.0,
         }
     }
+
+    pub fn with_weight(mut self, weight: Scalar) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn with_radius(mut self, radius: Scalar) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    /// Returns whether a chunk of the given size centered at `center` is detailed enough for this
+    /// observer.
+    fn is_satisfied(&self, center: Vector, size: Scalar, split_factor: Scalar) -> bool {
+        center.distance(self.position) - self.radius > size * split_factor * self.weight
+    }
+
+    /// Returns the ratio of a chunk's size to its distance from the observer, scaled by the
+    /// observer's weight. This grows with the error the chunk shows on screen.
+    pub fn screen_space_error(&self, center: Vector, size: Scalar) -> Scalar {
+        let distance = (center.distance(self.position) - self.radius).max(Scalar::EPSILON);
+        size * self.weight / distance
+    }
 }
 
-pub type CubeTreeNode = QuadTreeNode<ChunkData>;
+/// Chunks that changed in a [`CubeTree`] since the changes were last taken.
+#[derive(Clone, Debug, Default)]
+pub struct CubeTreeChanges {
+    /// Leaves created by splitting or merging nodes.
+    pub added: HashSet<ChunkHash>,
+    /// Leaves that no longer exist.
+    pub removed: HashSet<ChunkHash>,
+    /// Existing leaves whose [`ChunkData::edge_lods`] changed.
+    pub edge_lods_changed: HashSet<ChunkHash>,
+}
+
+impl CubeTreeChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.edge_lods_changed.is_empty()
+    }
+
+    /// Folds later changes into these ones, cancelling leaves that were added and then removed.
+    pub fn append(&mut self, other: Self) {
+        for hash in other.removed {
+            self.edge_lods_changed.remove(&hash);
+            if !self.added.remove(&hash) {
+                self.removed.insert(hash);
+            }
+        }
+        for hash in other.added {
+            if self.removed.remove(&hash) {
+                self.edge_lods_changed.insert(hash);
+            } else {
+                self.added.insert(hash);
+            }
+        }
+        self.edge_lods_changed.extend(
+            other
+                .edge_lods_changed
+                .into_iter()
+                .filter(|hash| !self.added.contains(hash)),
+        );
+    }
+}
 
 #[derive(Component, Clone, Debug)]
 pub struct CubeTree {
     pub radius: Scalar,
+    pub height: Heightfield,
     pub faces: [CubeTreeNode; 6],
+    /// Whether [`CubeTree::insert`] and [`CubeTree::update`] enforce a 2:1 level difference
+    /// between neighbouring leaves.
+    pub balanced: bool,
+    /// Level of detail tuning; call [`CubeTree::refresh`] after changing it, and keep it
+    /// [`LodSettings::clamped`].
+    pub settings: LodSettings,
+    observers: Vec<Observer>,
+    changes: CubeTreeChanges,
 }
 
 #[allow(unused)]
 impl CubeTree {
-    const MIN_SIZE: Scalar = 24.0;
-    const THRESHOLD: Scalar = 1.5;
-    const COLLIDER_RADIUS: Scalar = 24.0;
-
-    pub fn new(radius: Scalar) -> Self {
+    pub fn new(radius: Scalar, height: Heightfield) -> Self {
         let bounds = Rectangle::from_center_half_size(Vector2::ZERO, Vector2::splat(radius));
-        Self {
+        let mut tree = Self {
             radius,
             faces: Axis::ALL.map(|axis| {
                 let hash = ChunkHash::new_root(axis);
@@ -277,14 +631,48 @@ impl CubeTree {
                         axis,
                         bounds,
                         radius,
+                        &*height,
                         hash.with_depth(1).push_quadrant(quadrant),
                     )
                 })
             }),
-        }
+            height,
+            balanced: false,
+            settings: LodSettings::for_radius(radius),
+            observers: Vec::new(),
+            changes: CubeTreeChanges::default(),
+        };
+        tree.changes.added = tree.iter().map(|(_, data)| data.hash).collect();
+        tree
+    }
+
+    pub fn with_balancing(mut self, balanced: bool) -> Self {
+        self.balanced = balanced;
+        self
+    }
+
+    /// Sets the level of detail tuning, see [`LodSettings::clamped`].
+    pub fn with_settings(mut self, settings: LodSettings) -> Self {
+        self.settings = settings.clamped();
+        self
     }
 
+    /// Rebuilds the tree from scratch around `point`.
+    ///
+    /// Prefer [`CubeTree::update`], which only touches nodes whose level of detail changed.
     pub fn insert(&mut self, point: Vector) {
+        self.rebuild(vec![Observer::new(point)]);
+    }
+
+    /// Rebuilds the tree from scratch around the current observers, picking up changes to
+    /// [`CubeTree::settings`].
+    pub fn refresh(&mut self) {
+        self.rebuild(self.observers.clone());
+    }
+
+    fn rebuild(&mut self, observers: Vec<Observer>) {
+        self.observers = observers;
+        let removed = self.iter().map(|(bounds, data)| (*bounds, *data)).collect();
         let bounds = Rectangle::from_center_half_size(Vector2::ZERO, Vector2::splat(self.radius));
         for axis in Axis::ALL {
             let hash = ChunkHash::new_root(axis);
@@ -293,32 +681,324 @@ impl CubeTree {
                     axis,
                     bounds,
                     self.radius,
+                    &*self.height,
                     hash.with_depth(1).push_quadrant(quadrant),
                 )
             });
             new_node.insert_mut(
-                |(bounds, data)| {
-                    let size = bounds.size().x;
-                    if size <= Self::MIN_SIZE {
-                        data.hash = data.hash.with_collider(true);
-                        return true;
-                    }
-                    if data.center.distance(point) > size * Self::THRESHOLD {
-                        return true;
-                    }
-                    false
-                },
+                |(bounds, data)| Self::keep_leaf(&self.settings, &self.observers, bounds, data),
                 |(quadrant, bounds, data)| {
                     ChunkData::new(
                         axis,
                         bounds,
                         self.radius,
+                        &*self.height,
                         data.hash.increment_depth().push_quadrant(quadrant),
                     )
                 },
             );
             self[axis] = new_node;
         }
+        if self.balanced {
+            self.balance();
+        }
+        self.update_edge_lods();
+
+        let added = self.iter().map(|(bounds, data)| (*bounds, *data)).collect();
+        self.record_changes(
+            [
+                QuadTreeDiff {
+                    added: Vec::new(),
+                    removed,
+                },
+                QuadTreeDiff {
+                    added,
+                    removed: Vec::new(),
+                },
+            ],
+            Vec::new(),
+        );
+    }
+
+    /// Refines the tree around `observers`, splitting and merging only the nodes whose level of
+    /// detail changed. The affected chunks are accumulated until [`CubeTree::take_changes`].
+    pub fn update(&mut self, observers: &[Observer]) {
+        self.observers = observers.to_vec();
+        let mut diff = QuadTreeDiff::default();
+        let radius = self.radius;
+        for (axis, face) in Axis::ALL.into_iter().zip(self.faces.iter_mut()) {
+            // Face roots always stay split, as in `insert`.
+            let CubeTreeNode::Internal { children, .. } = face else {
+                continue;
+            };
+            for child in children {
+                child.update(
+                    |(bounds, data)| Self::keep_leaf(&self.settings, &self.observers, bounds, data),
+                    |(quadrant, bounds, data)| {
+                        ChunkData::new(
+                            axis,
+                            bounds,
+                            radius,
+                            &*self.height,
+                            data.hash.increment_depth().push_quadrant(quadrant),
+                        )
+                    },
+                    // Merging only needs the center of the merged chunk, so its full data is
+                    // sampled once a merge is decided.
+                    |bounds| {
+                        let depth = ChunkHash::from_bounds(axis, bounds, radius).depth();
+                        let direction = center_on_sphere(axis, radius, bounds) / radius;
+                        let center = direction * (radius + self.height.height(direction));
+                        Self::is_detailed_enough(
+                            &self.settings,
+                            &self.observers,
+                            bounds.size().x,
+                            depth,
+                            center,
+                        )
+                    },
+                    |bounds| {
+                        let hash = ChunkHash::from_bounds(axis, bounds, radius);
+                        let hash = hash.with_collider(hash.depth() >= self.settings.collider_depth);
+                        ChunkData::new(axis, bounds, radius, &*self.height, hash)
+                    },
+                    &mut diff,
+                );
+            }
+        }
+        let balanced = if self.balanced {
+            self.balance()
+        } else {
+            QuadTreeDiff::default()
+        };
+        let edge_lods_changed = self.update_edge_lods();
+        self.record_changes([diff, balanced], edge_lods_changed);
+    }
+
+    /// Returns the chunks that changed since the last call to [`CubeTree::take_changes`].
+    pub fn changes(&self) -> &CubeTreeChanges {
+        &self.changes
+    }
+
+    /// Takes the chunks that changed since the last call, leaving no pending changes.
+    pub fn take_changes(&mut self) -> CubeTreeChanges {
+        std::mem::take(&mut self.changes)
+    }
+
+    /// Returns the leaf with the given hash, if it is part of the tree.
+    pub fn get(&self, hash: ChunkHash) -> Option<(&Rectangle, &ChunkData)> {
+        self.leaf_at(hash.axis(), hash.bounds(self.radius).center())
+            .filter(|(_, data)| data.hash == hash)
+    }
+
+    /// Returns the leaves covering the area of the chunk with the given hash, whether or not that
+    /// chunk is part of the tree. This is either the chunk itself, the coarser leaf it was merged
+    /// into, or the finer leaves it was split into.
+    pub fn leaves_overlapping(&self, hash: ChunkHash) -> Vec<(&Rectangle, &ChunkData)> {
+        let mut leaves = Vec::new();
+        self[hash.axis()].find_leaves(&hash.bounds(self.radius), &mut leaves);
+        leaves
+    }
+
+    /// Returns the observers of the last [`CubeTree::insert`] or [`CubeTree::update`].
+    pub fn observers(&self) -> &[Observer] {
+        &self.observers
+    }
+
+    /// Decides whether a node stays a leaf for all `observers`, flagging chunks deep enough for
+    /// colliders.
+    fn keep_leaf(
+        settings: &LodSettings,
+        observers: &[Observer],
+        bounds: &Rectangle,
+        data: &mut ChunkData,
+    ) -> bool {
+        let depth = data.hash.depth();
+        data.hash = data.hash.with_collider(depth >= settings.collider_depth);
+        Self::is_detailed_enough(settings, observers, bounds.size().x, depth, data.center)
+    }
+
+    /// Returns whether a chunk of the given size and depth centered at `center` needs no more
+    /// detail for any of the observers.
+    fn is_detailed_enough(
+        settings: &LodSettings,
+        observers: &[Observer],
+        size: Scalar,
+        depth: u8,
+        center: Vector,
+    ) -> bool {
+        if size <= settings.min_chunk_size || depth >= settings.max_depth {
+            return true;
+        }
+        observers
+            .iter()
+            .all(|observer| observer.is_satisfied(center, size, settings.split_factor))
+    }
+
+    /// Resolves the leaves touched by consecutive structural changes against the current tree
+    /// and adds them to the pending changes.
+    fn record_changes(
+        &mut self,
+        diffs: impl IntoIterator<Item = QuadTreeDiff<ChunkData>>,
+        edge_lods_changed: Vec<ChunkHash>,
+    ) {
+        // The edge LODs of every touched leaf that existed before the first change.
+        let mut previous: HashMap<ChunkHash, Option<EdgeLods>> = HashMap::new();
+        for diff in diffs {
+            // Within one diff, a leaf that is both added and removed was created first.
+            let added: HashSet<ChunkHash> = diff.added.iter().map(|(_, data)| data.hash).collect();
+            for (_, data) in diff.removed.iter() {
+                if !added.contains(&data.hash) {
+                    previous.entry(data.hash).or_insert(Some(data.edge_lods));
+                }
+            }
+            for hash in added {
+                previous.entry(hash).or_insert(None);
+            }
+        }
+
+        let mut changes = CubeTreeChanges::default();
+        for (hash, lods) in previous.iter() {
+            match (self.get(*hash), lods) {
+                (Some((_, data)), Some(lods)) if data.edge_lods != *lods => {
+                    changes.edge_lods_changed.insert(*hash);
+                }
+                (Some(_), Some(_)) => {}
+                (Some(_), None) => {
+                    changes.added.insert(*hash);
+                }
+                (None, Some(_)) => {
+                    changes.removed.insert(*hash);
+                }
+                (None, None) => {}
+            }
+        }
+        changes.edge_lods_changed.extend(
+            edge_lods_changed
+                .into_iter()
+                .filter(|hash| !previous.contains_key(hash)),
+        );
+        self.changes.append(changes);
+    }
+
+    /// Splits leaves until no leaf has a neighbour more than one level coarser than itself,
+    /// including neighbours on adjacent cube faces.
+    ///
+    /// Splitting a leaf can unbalance its own neighbours, so this repeats until the tree settles.
+    /// Returns the leaves that were split and created.
+    pub fn balance(&mut self) -> QuadTreeDiff<ChunkData> {
+        let mut diff = QuadTreeDiff::default();
+        loop {
+            let tree = &*self;
+            let splits: Vec<(Axis, Vector2, u8)> = tree
+                .iter()
+                .flat_map(|(bounds, data)| {
+                    let depth = data.hash.depth();
+                    Edge::ALL.into_iter().filter_map(move |edge| {
+                        let (neighbor_bounds, neighbor) =
+                            tree.leaf_across(data.hash.axis(), bounds, edge)?;
+                        let neighbor_depth = neighbor.hash.depth();
+                        (neighbor_depth + 1 < depth).then(|| {
+                            (
+                                neighbor.hash.axis(),
+                                neighbor_bounds.center(),
+                                neighbor_depth,
+                            )
+                        })
+                    })
+                })
+                .collect();
+            if splits.is_empty() {
+                return diff;
+            }
+
+            let radius = self.radius;
+            for (axis, center, depth) in splits {
+                // The same coarse leaf may be reported by several finer neighbours.
+                let Some(node) = self.faces[axis as usize].find_leaf_node_mut(center) else {
+                    continue;
+                };
+                let Some(&data) = node.data().filter(|data| data.hash.depth() == depth) else {
+                    continue;
+                };
+                diff.removed.push((*node.bounds(), data));
+                let collider_depth = self.settings.collider_depth;
+                node.subdivide(|(quadrant, bounds, data)| {
+                    let hash = data
+                        .hash
+                        .increment_depth()
+                        .push_quadrant(quadrant)
+                        .with_collider(depth + 1 >= collider_depth);
+                    ChunkData::new(axis, bounds, radius, &*self.height, hash)
+                });
+                diff.added
+                    .extend(node.iter().map(|(bounds, data)| (*bounds, *data)));
+            }
+        }
+    }
+
+    /// Returns the leaf containing `point` on the face of `axis`.
+    pub fn leaf_at(&self, axis: Axis, point: Vector2) -> Option<(&Rectangle, &ChunkData)> {
+        self[axis].find_leaf(point)
+    }
+
+    /// Returns the leaf directly across `edge` of the chunk with the given bounds, wrapping onto
+    /// the adjacent cube face when the edge lies on a face border.
+    ///
+    /// If the neighbouring area is split finer than the chunk, one of the finer leaves touching
+    /// the middle of the edge is returned.
+    pub fn leaf_across(
+        &self,
+        axis: Axis,
+        bounds: &Rectangle,
+        edge: Edge,
+    ) -> Option<(&Rectangle, &ChunkData)> {
+        let half_size = bounds.half_size();
+        let epsilon = half_size.x.min(half_size.y) * 1e-3;
+        let point = bounds.center() + edge.offset() * (half_size + epsilon);
+        let face_pos = point / self.radius;
+        if face_pos.x.abs() <= 1.0 && face_pos.y.abs() <= 1.0 {
+            return self.leaf_at(axis, point);
+        }
+        let (neighbor_axis, neighbor_pos) = cube_to_face(face_to_cube(axis, face_pos));
+        self.leaf_at(neighbor_axis, neighbor_pos * self.radius)
+    }
+
+    /// Returns the leaves across each [`Edge`] of the chunk with the given hash, indexed by
+    /// `Edge as usize`. See [`CubeTree::leaf_across`] for how finer neighbours are resolved.
+    pub fn neighbors(&self, hash: ChunkHash) -> [Option<(&Rectangle, &ChunkData)>; 4] {
+        let bounds = hash.bounds(self.radius);
+        Edge::ALL.map(|edge| self.leaf_across(hash.axis(), &bounds, edge))
+    }
+
+    /// Recomputes [`ChunkData::edge_lods`] for every leaf from the current tree layout, returning
+    /// the hashes of the leaves whose edge LODs changed.
+    pub fn update_edge_lods(&mut self) -> Vec<ChunkHash> {
+        let edge_lods: HashMap<ChunkHash, EdgeLods> = self
+            .iter()
+            .map(|(bounds, data)| {
+                let depth = data.hash.depth();
+                let lods = Edge::ALL.map(|edge| {
+                    self.leaf_across(data.hash.axis(), bounds, edge)
+                        .map_or(0, |(_, neighbor)| {
+                            depth.saturating_sub(neighbor.hash.depth())
+                        })
+                });
+                (data.hash, EdgeLods(lods))
+            })
+            .collect();
+
+        let mut changed = Vec::new();
+        for face in self.faces.iter_mut() {
+            for (_, data) in face.iter_mut() {
+                let lods = edge_lods.get(&data.hash).copied().unwrap_or_default();
+                if data.edge_lods != lods {
+                    data.edge_lods = lods;
+                    changed.push(data.hash);
+                }
+            }
+        }
+        changed
     }
 
     pub fn iter(&self) -> CubeTreeIter {
@@ -481,13 +1161,13 @@ mod tests {
         ];
 
         // Create a new ChunkHash
-        let hash = ChunkHash::new(axis, depth, collider, path);
+        let hash = ChunkHash::new(axis, depth, collider, &path);
 
         // Verify by extracting fields
         assert_eq!(hash.axis(), axis);
         assert_eq!(hash.depth(), depth);
         assert_eq!(hash.collider(), collider);
-        assert_eq!(hash.path(), path);
+        assert_eq!(hash.path()[..path.len()], path);
     }
 
     #[test]
@@ -498,7 +1178,7 @@ mod tests {
         assert_eq!(hash.axis(), axis);
         assert_eq!(hash.depth(), 0);
         assert_eq!(hash.collider(), false);
-        assert_eq!(hash.path(), [Quadrant::ROOT; 7]);
+        assert_eq!(hash.path(), [Quadrant::ROOT; ChunkHash::PATH_CAPACITY]);
     }
 
     #[test]
@@ -517,13 +1197,13 @@ mod tests {
             Quadrant::SW,
         ];
 
-        let initial_hash = ChunkHash::new(axis, depth, collider, path);
+        let initial_hash = ChunkHash::new(axis, depth, collider, &path);
 
         // Push a new quadrant to the front
         let new_quadrant = Quadrant::SE;
         let updated_hash = initial_hash.push_quadrant(new_quadrant);
 
-        // Expected new path: the new quadrant at the front, and the last one dropped
+        // Expected new path: the new quadrant at the front, followed by the old path
         let expected_path = [
             Quadrant::SE,
             Quadrant::NW,
@@ -532,10 +1212,11 @@ mod tests {
             Quadrant::SE,
             Quadrant::NW,
             Quadrant::NE,
+            Quadrant::SW,
         ];
 
         // Verify the path was updated correctly
-        assert_eq!(updated_hash.path(), expected_path);
+        assert_eq!(updated_hash.path()[..expected_path.len()], expected_path);
 
         // Verify other fields remained unchanged
         assert_eq!(updated_hash.axis(), axis);
@@ -571,9 +1252,9 @@ mod tests {
         let axis = Axis::Z;
         let depth = 7;
         let collider = true;
-        let path = [Quadrant::ROOT; 7];
+        let path = [Quadrant::ROOT; ChunkHash::PATH_CAPACITY];
 
-        let hash = ChunkHash::new(axis, depth, collider, path);
+        let hash = ChunkHash::new(axis, depth, collider, &path);
         let (extracted_axis, extracted_depth, extracted_path, extracted_flag) = hash.values();
 
         assert_eq!(extracted_axis, axis);
@@ -584,10 +1265,309 @@ mod tests {
         assert_eq!(extracted_flag, collider);
     }
 
+    #[test]
+    fn test_leaf_across_is_symmetric() {
+        let mut tree = CubeTree::new(1000.0, Heightfield::default());
+        tree.insert(Vector::new(1.0, 0.4, -0.2).normalize() * 1000.0);
+
+        for (bounds, data) in tree.iter() {
+            for edge in Edge::ALL {
+                let (neighbor_bounds, neighbor) = tree
+                    .leaf_across(data.hash.axis(), bounds, edge)
+                    .expect("expected every edge to have a neighbour");
+                if neighbor.hash.depth() != data.hash.depth() {
+                    continue;
+                }
+                assert!(
+                    Edge::ALL.iter().any(|&back| {
+                        tree.leaf_across(neighbor.hash.axis(), neighbor_bounds, back)
+                            .is_some_and(|(_, other)| other.hash == data.hash)
+                    }),
+                    "{:?} is not a neighbour of its neighbour {:?}",
+                    data.hash.values(),
+                    neighbor.hash.values()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_chunk_hash_coordinates_round_trip() {
+        let hash = ChunkHash::new_root(Axis::NegY)
+            .with_depth(3)
+            .push_quadrant(Quadrant::NE)
+            .push_quadrant(Quadrant::SW)
+            .push_quadrant(Quadrant::SE);
+        assert_eq!(hash.coordinates(), U64Vec2::new(0b101, 0b100));
+        assert_eq!(
+            ChunkHash::from_coordinates(Axis::NegY, 3, false, hash.coordinates()),
+            hash
+        );
+    }
+
+    #[test]
+    fn test_chunk_hash_neighbor() {
+        let hash = ChunkHash::from_coordinates(Axis::Z, 2, false, U64Vec2::new(1, 1));
+        assert_eq!(hash.neighbor(Edge::North).coordinates(), U64Vec2::new(1, 2));
+        assert_eq!(hash.neighbor(Edge::West).coordinates(), U64Vec2::new(0, 1));
+
+        for axis in Axis::ALL {
+            for depth in 0..4 {
+                let cells = 1u64 << depth;
+                for x in 0..cells {
+                    for y in 0..cells {
+                        let hash =
+                            ChunkHash::from_coordinates(axis, depth, false, U64Vec2::new(x, y));
+                        for edge in Edge::ALL {
+                            let neighbor = hash.neighbor(edge);
+                            assert_eq!(neighbor.depth(), depth);
+                            assert_ne!(neighbor, hash);
+                            assert!(
+                                Edge::ALL
+                                    .iter()
+                                    .any(|&back| neighbor.neighbor(back) == hash),
+                                "{:?} is not a neighbour of its neighbour {:?}",
+                                hash.values(),
+                                neighbor.values()
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_neighbors_match_chunk_hash_neighbor() {
+        let tree = CubeTree::new(1000.0, Heightfield::default());
+        for (_, data) in tree.iter() {
+            for (edge, neighbor) in Edge::ALL.into_iter().zip(tree.neighbors(data.hash)) {
+                let (_, neighbor) = neighbor.expect("expected every edge to have a neighbour");
+                assert_eq!(neighbor.hash, data.hash.neighbor(edge));
+            }
+        }
+    }
+
+    #[test]
+    fn test_update_matches_insert_and_reports_changes() {
+        let first = Vector::new(1.0, 0.4, -0.2).normalize() * 1000.0;
+        let second = Vector::new(-0.3, 1.0, 0.5).normalize() * 1000.0;
+
+        let mut tree = CubeTree::new(1000.0, Heightfield::default()).with_balancing(true);
+        tree.update(&[Observer::new(first)]);
+        let mut leaves: HashSet<ChunkHash> = tree.take_changes().added;
+        assert_eq!(leaves, tree.iter().map(|(_, data)| data.hash).collect());
+
+        tree.update(&[Observer::new(second)]);
+        let changes = tree.take_changes();
+        assert!(changes.added.is_disjoint(&changes.removed));
+        for hash in changes.removed.iter() {
+            assert!(
+                leaves.remove(hash),
+                "removed {:?} was not a leaf",
+                hash.values()
+            );
+            // Whatever replaced a removed chunk covers its area.
+            let area = hash.bounds(1000.0);
+            let covered: Scalar = tree
+                .leaves_overlapping(*hash)
+                .iter()
+                .map(|(bounds, _)| bounds.intersect(area).size().element_product())
+                .sum();
+            assert!((covered - area.size().element_product()).abs() < 1e-6);
+        }
+        leaves.extend(changes.added.iter().copied());
+
+        let mut rebuilt = CubeTree::new(1000.0, Heightfield::default()).with_balancing(true);
+        rebuilt.insert(second);
+        let expected: HashSet<ChunkHash> = rebuilt.iter().map(|(_, data)| data.hash).collect();
+        assert_eq!(leaves, expected);
+        assert_eq!(leaves, tree.iter().map(|(_, data)| data.hash).collect());
+        for (_, data) in tree.iter() {
+            let (_, other) = rebuilt.get(data.hash).expect("expected the same leaves");
+            assert_eq!(data.edge_lods, other.edge_lods);
+        }
+
+        tree.update(&[Observer::new(second)]);
+        assert!(tree.changes().is_empty());
+    }
+
+    #[test]
+    fn test_update_refines_around_every_observer() {
+        let first = Vector::new(1.0, 0.4, -0.2).normalize() * 1000.0;
+        let second = Vector::new(-0.3, -1.0, 0.5).normalize() * 1000.0;
+        let depth_at = |tree: &CubeTree, point: Vector| {
+            let (axis, face_pos) = cube_to_face(point);
+            tree.leaf_at(axis, face_pos * 1000.0)
+                .map(|(_, data)| data.hash.depth())
+                .unwrap()
+        };
+
+        let mut tree = CubeTree::new(1000.0, Heightfield::default());
+        tree.update(&[Observer::new(first), Observer::new(second)]);
+        let first_depth = depth_at(&tree, first);
+        let second_depth = depth_at(&tree, second);
+        assert!(first_depth > 2);
+        assert!(second_depth > 2);
+
+        // A lower weight asks for less detail around the second observer.
+        tree.update(&[Observer::new(first), Observer::new(second).with_weight(0.1)]);
+        assert_eq!(depth_at(&tree, first), first_depth);
+        assert!(depth_at(&tree, second) < second_depth);
+    }
+
+    #[test]
+    fn test_balanced_neighbors_differ_by_one_level() {
+        let mut tree = CubeTree::new(1000.0, Heightfield::default()).with_balancing(true);
+        tree.insert(Vector::new(1.0, 1.0, 0.98).normalize() * 1000.0);
+
+        for (bounds, data) in tree.iter() {
+            for edge in Edge::ALL {
+                let (_, neighbor) = tree
+                    .leaf_across(data.hash.axis(), bounds, edge)
+                    .expect("expected every edge to have a neighbour");
+                assert!(
+                    data.hash.depth() <= neighbor.hash.depth() + 1,
+                    "{:?} at depth {} borders {:?} at depth {}",
+                    data.hash.values(),
+                    data.hash.depth(),
+                    neighbor.hash.values(),
+                    neighbor.hash.depth()
+                );
+            }
+        }
+    }
+
     #[test]
     #[should_panic(expected = "depth is too large for 6 bits")]
     fn test_depth_too_large() {
         // This should panic because depth > 63
-        ChunkHash::new(Axis::X, 64, false, [Quadrant::ROOT; 7]);
+        ChunkHash::new(Axis::X, 64, false, &[]);
+    }
+
+    #[test]
+    fn test_push_quadrant_keeps_full_capacity() {
+        let root = ChunkHash::new_root(Axis::NegZ);
+        let deep =
+            (0..ChunkHash::PATH_CAPACITY).fold(root, |hash, _| hash.push_quadrant(Quadrant::NE));
+        // Differs from `deep` only in the oldest quadrant of the path.
+        let other = (1..ChunkHash::PATH_CAPACITY)
+            .fold(root.push_quadrant(Quadrant::SW), |hash, _| {
+                hash.push_quadrant(Quadrant::NE)
+            });
+
+        assert_ne!(deep, other);
+        assert_eq!(deep.path()[ChunkHash::PATH_CAPACITY - 1], Quadrant::NE);
+        assert_eq!(other.path()[ChunkHash::PATH_CAPACITY - 1], Quadrant::SW);
+    }
+
+    #[test]
+    fn test_chunk_hashes_are_unique_across_the_tree() {
+        let radius = crate::constants::physics::EARTH_DIAMETER_M / 2.0;
+        let mut tree = CubeTree::new(radius, Heightfield::default());
+        tree.insert(Vector::new(0.3, -1.0, 0.2).normalize() * radius);
+
+        let mut hashes = bevy::utils::HashSet::new();
+        let mut max_depth = 0;
+        for (bounds, data) in tree.iter() {
+            assert!(
+                hashes.insert(data.hash),
+                "duplicate hash {:?}",
+                data.hash.values()
+            );
+            assert!(data.hash.bounds(radius).center().distance(bounds.center()) < 1e-3);
+            max_depth = max_depth.max(data.hash.depth());
+        }
+        assert!(
+            max_depth > 7,
+            "expected the tree to be deeper than the old path capacity"
+        );
+    }
+
+    /// A height source that rises toward the positive x axis and does not bound its variation.
+    struct Tilt;
+
+    impl HeightSource for Tilt {
+        fn height(&self, direction: Vector) -> Scalar {
+            direction.x * 100.0
+        }
+    }
+
+    #[test]
+    fn test_unbounded_height_sources_keep_horizon_culling() {
+        use crate::plugins::terrain::culling::Horizon;
+
+        let radius = 1000.0;
+        let mut tree = CubeTree::new(radius, Heightfield::new(Tilt));
+        let observer = Vector::new(1.0, 0.4, -0.2).normalize() * (radius + 150.0);
+        tree.insert(observer);
+
+        for (bounds, data) in tree.iter() {
+            assert!(data.min_elevation.is_finite() && data.max_elevation.is_finite());
+            for y in 0..=8 {
+                for x in 0..=8 {
+                    let t = Vector2::new(x as Scalar, y as Scalar) / 8.0;
+                    let face_pos = (bounds.min + bounds.size() * t) / radius;
+                    let direction =
+                        unit_cube_to_sphere(face_to_cube(data.hash.axis(), face_pos)).normalize();
+                    let elevation = Tilt.height(direction);
+                    assert!(
+                        (data.min_elevation..=data.max_elevation).contains(&elevation),
+                        "{elevation} is outside {}..{}",
+                        data.min_elevation,
+                        data.max_elevation
+                    );
+                }
+            }
+        }
+
+        let min_elevation = tree
+            .iter()
+            .map(|(_, data)| data.min_elevation)
+            .fold(Scalar::INFINITY, Scalar::min);
+        let horizon = Horizon::new(observer, radius + min_elevation);
+        assert!(tree
+            .iter()
+            .any(|(_, data)| !horizon.is_visible(data, radius)));
+    }
+
+    #[test]
+    fn test_max_depth_is_clamped_to_path_capacity() {
+        let settings = LodSettings {
+            min_chunk_size: 0.0,
+            max_depth: 63,
+            ..LodSettings::for_radius(1000.0)
+        };
+        let mut tree = CubeTree::new(1000.0, Heightfield::default()).with_settings(settings);
+        assert_eq!(tree.settings.max_depth as usize, ChunkHash::PATH_CAPACITY);
+        tree.insert(Vector::new(0.3, -1.0, 0.2).normalize() * 1000.0);
+
+        let mut hashes = bevy::utils::HashSet::new();
+        for (_, data) in tree.iter() {
+            assert!(data.hash.depth() as usize <= ChunkHash::PATH_CAPACITY);
+            assert!(
+                hashes.insert(data.hash),
+                "duplicate hash {:?}",
+                data.hash.values()
+            );
+        }
+    }
+
+    #[test]
+    fn test_chunk_hashes_are_unique_per_cell() {
+        let mut hashes = bevy::utils::HashSet::new();
+        for axis in Axis::ALL {
+            for depth in 0..5 {
+                let cells = 1u64 << depth;
+                for x in 0..cells {
+                    for y in 0..cells {
+                        let coordinates = U64Vec2::new(x, y);
+                        let hash = ChunkHash::from_coordinates(axis, depth, false, coordinates);
+                        assert_eq!(hash.coordinates(), coordinates);
+                        assert!(hashes.insert(hash), "duplicate hash {:?}", hash.values());
+                    }
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,197 @@
+use avian3d::math::{AdjustPrecision, AsF32, Scalar, Vector, PI};
+use bevy::math::Vec3A;
+use bevy::prelude::*;
+use bevy::render::primitives::{Aabb, Frustum, Sphere};
+
+use super::cube_tree::ChunkData;
+
+/// Returns a sphere enclosing the surface of a chunk on a body of the given radius, as its
+/// center and radius.
+pub fn chunk_bounding_sphere(data: &ChunkData, radius: Scalar) -> (Vector, Scalar) {
+    let direction = data.center.normalize();
+    let mid_elevation = (data.min_elevation + data.max_elevation) / 2.0;
+    // Points on the chunk are at most an arc of the angular radius away from the center
+    // direction, and at most half the elevation range above or below the middle.
+    let bounding_radius = (radius + data.max_elevation) * data.angular_radius
+        + (data.max_elevation - data.min_elevation) / 2.0;
+    (direction * (radius + mid_elevation), bounding_radius)
+}
+
+/// Returns the chunk with its elevation range raised to cover an ocean at `sea_level`, so that
+/// culling keeps chunks whose water can be seen even where their terrain cannot.
+pub fn with_ocean(data: &ChunkData, sea_level: Option<Scalar>) -> ChunkData {
+    let mut data = *data;
+    if let Some(sea_level) = sea_level {
+        data.max_elevation = data.max_elevation.max(sea_level);
+    }
+    data
+}
+
+/// Returns a box enclosing the surface of a chunk, relative to the chunk's center.
+///
+/// Bevy computes the bounds of a mesh from its positions, which compact chunks do not have, see
+/// [`VertexMode::Compact`](super::mesh::VertexMode::Compact).
+pub fn chunk_aabb(data: &ChunkData, radius: Scalar) -> Aabb {
+    let (center, bounding_radius) = chunk_bounding_sphere(data, radius);
+    Aabb {
+        center: (center - data.center).f32().into(),
+        half_extents: Vec3A::splat(bounding_radius as f32),
+    }
+}
+
+/// The horizon of an observer, formed by a sphere that the terrain never dips below.
+pub struct Horizon {
+    direction: Vector,
+    occluder_radius: Scalar,
+    /// Angle, as seen from the center of the body, between the observer and its horizon.
+    angle: Scalar,
+}
+
+impl Horizon {
+    pub fn new(observer: Vector, occluder_radius: Scalar) -> Self {
+        let distance = observer.length();
+        // Observers below the occluder, or infinitely far away, see everything.
+        let angle = if distance.is_finite() && distance > occluder_radius {
+            (occluder_radius / distance).acos()
+        } else {
+            PI
+        };
+        Self {
+            direction: observer / distance,
+            occluder_radius,
+            angle,
+        }
+    }
+
+    /// Returns whether any part of a chunk may rise above the horizon.
+    ///
+    /// A point at `max_radius` from the center of the body is visible as long as it lies within
+    /// the observer's horizon angle plus its own, so the chunk is culled only when its nearest
+    /// edge is past both.
+    pub fn is_visible(&self, data: &ChunkData, radius: Scalar) -> bool {
+        if self.angle >= PI {
+            return true;
+        }
+
+        let max_radius = radius + data.max_elevation;
+        let peak_angle = (self.occluder_radius / max_radius).clamp(0.0, 1.0).acos();
+        let angle = self.direction.angle_between(data.center) - data.angular_radius;
+        angle <= self.angle + peak_angle
+    }
+}
+
+/// A camera's view frustum in the local space of a body.
+///
+/// The frustum is centered on the camera so that chunks far from the floating origin can be
+/// tested in `f32` without losing precision.
+pub struct ChunkFrustum {
+    frustum: Frustum,
+    position: Vector,
+    margin_sin: Scalar,
+    margin_distance: Scalar,
+    keep_distance: Scalar,
+}
+
+impl ChunkFrustum {
+    pub fn new(
+        camera: &Camera,
+        camera_transform: &GlobalTransform,
+        body_transform: &GlobalTransform,
+    ) -> Self {
+        let body_rotation = body_transform.rotation();
+        let position = (body_rotation.inverse()
+            * (camera_transform.translation() - body_transform.translation()))
+        .adjust_precision();
+        let view_from_local =
+            Mat4::from_quat(camera_transform.rotation().inverse() * body_rotation);
+        Self {
+            frustum: Frustum::from_clip_from_world(&(camera.clip_from_view() * view_from_local)),
+            position,
+            margin_sin: 0.0,
+            margin_distance: 0.0,
+            keep_distance: 0.0,
+        }
+    }
+
+    /// Returns the camera's position in the body's local space.
+    pub fn position(&self) -> Vector {
+        self.position
+    }
+
+    /// Widens the frustum so that it still contains what the camera sees after turning by up to
+    /// `angle` radians and moving by up to `distance` meters.
+    pub fn with_margin(mut self, angle: Scalar, distance: Scalar) -> Self {
+        self.margin_sin = angle.min(PI / 2.0).sin();
+        self.margin_distance = distance;
+        self
+    }
+
+    /// Keeps spheres within `distance` of the camera inside the frustum, wherever it looks.
+    pub fn with_keep_distance(mut self, distance: Scalar) -> Self {
+        self.keep_distance = distance;
+        self
+    }
+
+    /// Returns whether the sphere, in the body's local space, is at least partially inside the
+    /// frustum or its margin. The far plane is ignored.
+    pub fn intersects_sphere(&self, center: Vector, radius: Scalar) -> bool {
+        let distance = center.distance(self.position);
+        if distance - radius <= self.keep_distance {
+            return true;
+        }
+        // Turning the camera sweeps its planes by at most the margin angle, which moves them
+        // this far at the sphere's distance.
+        let sphere = Sphere {
+            center: (center - self.position).f32().into(),
+            radius: (radius + self.margin_distance + distance * self.margin_sin) as f32,
+        };
+        self.frustum.intersects_sphere(&sphere, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::terrain::cube_tree::{Axis, ChunkHash, EdgeLods};
+
+    fn chunk(direction: Vector, max_elevation: Scalar) -> ChunkData {
+        ChunkData {
+            center: direction.normalize() * 1000.0,
+            hash: ChunkHash::new_root(Axis::X),
+            edge_lods: EdgeLods::NONE,
+            min_elevation: 0.0,
+            max_elevation,
+            angular_radius: 0.01,
+        }
+    }
+
+    #[test]
+    fn test_horizon_accounts_for_elevation() {
+        // From 10m up, the horizon of a 1000m sphere is about 8 degrees away.
+        let horizon = Horizon::new(Vector::X * 1010.0, 1000.0);
+        let past_horizon = Vector::new(1.0, 0.3, 0.0);
+        assert!(horizon.is_visible(&chunk(Vector::X, 0.0), 1000.0));
+        assert!(!horizon.is_visible(&chunk(past_horizon, 0.0), 1000.0));
+        assert!(horizon.is_visible(&chunk(past_horizon, 100.0), 1000.0));
+        assert!(!horizon.is_visible(&chunk(-Vector::X, 100.0), 1000.0));
+
+        // Observers inside the occluder see everything.
+        let inside = Horizon::new(Vector::X * 900.0, 1000.0);
+        assert!(inside.is_visible(&chunk(-Vector::X, 0.0), 1000.0));
+    }
+
+    #[test]
+    fn test_ocean_keeps_submerged_chunks_visible() {
+        let horizon = Horizon::new(Vector::X * 1010.0, 950.0);
+        let mut seabed = chunk(Vector::new(1.0, 0.7, 0.0), -40.0);
+        seabed.min_elevation = -50.0;
+        assert!(!horizon.is_visible(&seabed, 1000.0));
+        assert!(!horizon.is_visible(&with_ocean(&seabed, None), 1000.0));
+        assert!(horizon.is_visible(&with_ocean(&seabed, Some(0.0)), 1000.0));
+
+        // The ocean widens the bounding sphere up to the surface.
+        let (center, bounding_radius) =
+            chunk_bounding_sphere(&with_ocean(&seabed, Some(0.0)), 1000.0);
+        assert!(center.length() + bounding_radius >= 1000.0);
+    }
+}
@@ -0,0 +1,344 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::mesh::{Indices, MeshVertexAttribute, PrimitiveTopology, VertexAttributeValues},
+    render::render_resource::VertexFormat,
+};
+use std::hash::Hasher;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::cube_tree::{ChunkHash, EdgeLods};
+use super::mesh::{
+    ATTRIBUTE_ALTITUDE_SLOPE, ATTRIBUTE_BIOME_WEIGHTS, ATTRIBUTE_MORPH, ATTRIBUTE_MORPH_NORMAL,
+    ATTRIBUTE_TEXTURE_POSITION,
+};
+
+const MAGIC: [u8; 4] = *b"PPCM";
+
+/// Bumped whenever the file layout or the meshes built for the same parameters change.
+const FORMAT_VERSION: u32 = 8;
+
+/// The vertex attributes stored for a chunk, referenced by their position in this list.
+const CHUNK_ATTRIBUTES: [MeshVertexAttribute; 9] = [
+    Mesh::ATTRIBUTE_POSITION,
+    Mesh::ATTRIBUTE_NORMAL,
+    Mesh::ATTRIBUTE_TANGENT,
+    Mesh::ATTRIBUTE_UV_0,
+    ATTRIBUTE_MORPH,
+    ATTRIBUTE_BIOME_WEIGHTS,
+    ATTRIBUTE_ALTITUDE_SLOPE,
+    ATTRIBUTE_TEXTURE_POSITION,
+    ATTRIBUTE_MORPH_NORMAL,
+];
+
+/// Numbers the temporary files of [`ChunkDiskCache::store`] within this process.
+static PARTIAL_FILES: AtomicU64 = AtomicU64::new(0);
+
+/// A 64-bit FNV-1a hasher, stable across runs and builds unlike the standard library's hashers.
+#[derive(Copy, Clone, Debug)]
+pub struct FingerprintHasher(u64);
+
+impl Default for FingerprintHasher {
+    fn default() -> Self {
+        Self(0xCBF2_9CE4_8422_2325)
+    }
+}
+
+impl Hasher for FingerprintHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3);
+        }
+    }
+}
+
+/// Persists generated chunk meshes on disk, so deterministic chunks are only generated once.
+///
+/// Insert this resource to enable the cache. Chunks are loaded and stored by the chunk jobs on
+/// the async compute pool. Every body writes to a directory named after the fingerprint of its
+/// generation parameters, see [`ChunkMeshBuilder::fingerprint`](super::mesh::ChunkMeshBuilder::fingerprint),
+/// so changing the height source, radius or mesh settings never loads stale chunks. Bodies
+/// whose height source has no fingerprint are not cached.
+#[derive(Resource, Clone, Debug)]
+pub struct ChunkDiskCache {
+    root: PathBuf,
+}
+
+impl ChunkDiskCache {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the file a chunk mesh is stored in. Chunks with and without a collider share it.
+    pub fn path(&self, fingerprint: u64, hash: ChunkHash, edge_lods: EdgeLods) -> PathBuf {
+        let [north, east, south, west] = edge_lods.0;
+        self.root
+            .join(format!("{fingerprint:016x}-v{FORMAT_VERSION}"))
+            .join(format!(
+                "{:032x}-{north:02x}{east:02x}{south:02x}{west:02x}.chunk",
+                hash.with_collider(false).to_bits()
+            ))
+    }
+
+    /// Loads a chunk mesh, returning `None` if it has not been stored.
+    pub fn load(
+        &self,
+        fingerprint: u64,
+        hash: ChunkHash,
+        edge_lods: EdgeLods,
+    ) -> io::Result<Option<Mesh>> {
+        match std::fs::read(self.path(fingerprint, hash, edge_lods)) {
+            Ok(bytes) => decode_mesh(&bytes).map(Some),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Stores a chunk mesh. The file is written next to its destination and then renamed, so
+    /// concurrent loads never see a partial mesh. Every write gets its own temporary file, so
+    /// jobs storing the same chunk at once, from this or another process, cannot interleave.
+    pub fn store(
+        &self,
+        fingerprint: u64,
+        hash: ChunkHash,
+        edge_lods: EdgeLods,
+        mesh: &Mesh,
+    ) -> io::Result<()> {
+        let path = self.path(fingerprint, hash, edge_lods);
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        let partial = path.with_extension(format!(
+            "{}-{}.partial",
+            std::process::id(),
+            PARTIAL_FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let result = std::fs::write(&partial, encode_mesh(mesh))
+            .and_then(|_| std::fs::rename(&partial, path));
+        if result.is_err() {
+            let _ = std::fs::remove_file(&partial);
+        }
+        result
+    }
+
+    /// Loads a chunk mesh, or builds and stores it if it has not been stored. Failing to read or
+    /// write the cache is logged and falls back to building the mesh.
+    pub fn load_or_build(
+        &self,
+        fingerprint: u64,
+        hash: ChunkHash,
+        edge_lods: EdgeLods,
+        build: impl FnOnce() -> Mesh,
+    ) -> Mesh {
+        match self.load(fingerprint, hash, edge_lods) {
+            Ok(Some(mesh)) => return mesh,
+            Ok(None) => {}
+            Err(error) => warn!("Failed to load chunk {hash:?} from disk: {error}"),
+        }
+        let mesh = build();
+        if let Err(error) = self.store(fingerprint, hash, edge_lods, &mesh) {
+            warn!("Failed to store chunk {hash:?} on disk: {error}");
+        }
+        mesh
+    }
+}
+
+/// Serializes the chunk attributes and indices of `mesh`, in native byte order.
+pub fn encode_mesh(mesh: &Mesh) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_ne_bytes());
+
+    let attributes: Vec<(u32, &VertexAttributeValues)> = CHUNK_ATTRIBUTES
+        .iter()
+        .enumerate()
+        .filter_map(|(index, attribute)| Some((index as u32, mesh.attribute(attribute.id)?)))
+        .collect();
+    bytes.extend_from_slice(&(attributes.len() as u32).to_ne_bytes());
+    for (index, values) in attributes {
+        let data = values.get_bytes();
+        bytes.extend_from_slice(&index.to_ne_bytes());
+        bytes.extend_from_slice(&(data.len() as u32).to_ne_bytes());
+        bytes.extend_from_slice(data);
+    }
+
+    let (format, data): (u8, Vec<u8>) = match mesh.indices() {
+        None => (0, Vec::new()),
+        Some(Indices::U16(indices)) => (1, indices.iter().flat_map(|i| i.to_ne_bytes()).collect()),
+        Some(Indices::U32(indices)) => (2, indices.iter().flat_map(|i| i.to_ne_bytes()).collect()),
+    };
+    bytes.push(format);
+    bytes.extend_from_slice(&(data.len() as u32).to_ne_bytes());
+    bytes.extend_from_slice(&data);
+    bytes
+}
+
+/// Deserializes a mesh written by [`encode_mesh`].
+pub fn decode_mesh(bytes: &[u8]) -> io::Result<Mesh> {
+    let mut reader = Reader(bytes);
+    if reader.take(MAGIC.len())? != MAGIC || reader.u32()? != FORMAT_VERSION {
+        return Err(invalid_data("not a chunk mesh of the current format"));
+    }
+
+    let mut mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    );
+    for _ in 0..reader.u32()? {
+        let attribute = CHUNK_ATTRIBUTES
+            .get(reader.u32()? as usize)
+            .ok_or_else(|| invalid_data("unknown vertex attribute"))?;
+        let len = reader.u32()? as usize;
+        let data = reader.take(len)?;
+        mesh.insert_attribute(attribute.clone(), decode_values(attribute.format, data)?);
+    }
+
+    let format = reader.take(1)?[0];
+    let len = reader.u32()? as usize;
+    let data = reader.take(len)?;
+    match format {
+        0 => {}
+        1 => mesh.insert_indices(Indices::U16(
+            data.chunks_exact(2)
+                .map(|i| u16::from_ne_bytes([i[0], i[1]]))
+                .collect(),
+        )),
+        2 => mesh.insert_indices(Indices::U32(
+            data.chunks_exact(4)
+                .map(|i| u32::from_ne_bytes([i[0], i[1], i[2], i[3]]))
+                .collect(),
+        )),
+        _ => return Err(invalid_data("unknown index format")),
+    }
+    Ok(mesh)
+}
+
+fn decode_values(format: VertexFormat, data: &[u8]) -> io::Result<VertexAttributeValues> {
+    Ok(match format {
+        VertexFormat::Float32x2 => VertexAttributeValues::Float32x2(decode_f32s(data)),
+        VertexFormat::Float32x3 => VertexAttributeValues::Float32x3(decode_f32s(data)),
+        VertexFormat::Float32x4 => VertexAttributeValues::Float32x4(decode_f32s(data)),
+        _ => return Err(invalid_data("unsupported vertex format")),
+    })
+}
+
+fn decode_f32s<const N: usize>(data: &[u8]) -> Vec<[f32; N]> {
+    data.chunks_exact(N * 4)
+        .map(|vertex| {
+            std::array::from_fn(|i| {
+                f32::from_ne_bytes([
+                    vertex[i * 4],
+                    vertex[i * 4 + 1],
+                    vertex[i * 4 + 2],
+                    vertex[i * 4 + 3],
+                ])
+            })
+        })
+        .collect()
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if len > self.0.len() {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Rectangle;
+    use crate::plugins::terrain::cube_tree::{Axis, ChunkData};
+    use crate::plugins::terrain::mesh::ChunkMeshBuilder;
+    use crate::plugins::terrain::{FractalNoise, Heightfield};
+    use avian3d::math::Vector2;
+
+    #[test]
+    fn test_decode_mesh_round_trips() {
+        let height = Heightfield::from(FractalNoise::MOON);
+        let builder = ChunkMeshBuilder::new(100.0, height.clone()).with_subdivisions(4);
+        let bounds = Rectangle::from_corners(Vector2::splat(-100.0), Vector2::ZERO);
+        let hash = ChunkHash::new_root(Axis::X).increment_depth();
+        let data = ChunkData::new(Axis::X, &bounds, 100.0, &*height, hash);
+        let mesh = builder.build(&bounds, &data);
+
+        let decoded = decode_mesh(&encode_mesh(&mesh)).expect("expected mesh to decode");
+        for attribute in CHUNK_ATTRIBUTES {
+            assert_eq!(
+                decoded
+                    .attribute(attribute.id)
+                    .map(VertexAttributeValues::get_bytes),
+                mesh.attribute(attribute.id)
+                    .map(VertexAttributeValues::get_bytes),
+            );
+        }
+        assert!(decoded
+            .indices()
+            .unwrap()
+            .iter()
+            .eq(mesh.indices().unwrap().iter()));
+        assert!(decode_mesh(&encode_mesh(&mesh)[..20]).is_err());
+    }
+
+    #[test]
+    fn test_concurrent_stores_of_a_chunk_stay_intact() {
+        let height = Heightfield::from(FractalNoise::MOON);
+        let builder = ChunkMeshBuilder::new(100.0, height.clone()).with_subdivisions(16);
+        let bounds = Rectangle::from_corners(Vector2::splat(-100.0), Vector2::ZERO);
+        let hash = ChunkHash::new_root(Axis::Y).increment_depth();
+        let data = ChunkData::new(Axis::Y, &bounds, 100.0, &*height, hash);
+        let mesh = builder.build(&bounds, &data);
+
+        let cache = ChunkDiskCache::new(std::env::temp_dir().join(format!(
+            "procedural_planet_disk_cache_test_{}",
+            std::process::id()
+        )));
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    cache
+                        .store(0, hash, EdgeLods::NONE, &mesh)
+                        .expect("expected chunk to be stored");
+                });
+            }
+        });
+
+        let loaded = cache
+            .load(0, hash, EdgeLods::NONE)
+            .expect("expected stored chunk to decode")
+            .expect("expected chunk to be stored");
+        assert_eq!(encode_mesh(&loaded), encode_mesh(&mesh));
+        let directory = cache.path(0, hash, EdgeLods::NONE);
+        let leftovers = std::fs::read_dir(directory.parent().unwrap())
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().extension() == Some("partial".as_ref()))
+            .count();
+        assert_eq!(leftovers, 0);
+        std::fs::remove_dir_all(cache.root()).unwrap();
+    }
+}
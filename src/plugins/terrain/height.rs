@@ -0,0 +1,309 @@
+use avian3d::math::{Scalar, Vector};
+use bevy::prelude::*;
+use std::hash::Hasher;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use super::disk_cache::FingerprintHasher;
+
+/// A source of terrain elevation for a [`Body`](super::Body).
+///
+/// Implementations are sampled by a direction on the unit sphere and return the elevation in
+/// meters relative to the body's radius. Sampling happens on the async compute pool, so sources
+/// must be cheap to share between threads and deterministic for a given direction.
+pub trait HeightSource: Send + Sync + 'static {
+    /// Returns the elevation in meters at the given unit-sphere `direction`.
+    fn height(&self, direction: Vector) -> Scalar;
+
+    /// Returns an upper bound on how much the elevation can change between two directions that
+    /// are `distance` apart on the unit sphere, if the source knows one.
+    ///
+    /// Chunk elevation bounds are widened by this much, so horizon culling stays conservative
+    /// between samples. Without a bound, chunks are widened by the spread of their samples
+    /// instead, which only misses features much narrower than the chunk.
+    fn max_variation(&self, _distance: Scalar) -> Option<Scalar> {
+        None
+    }
+
+    /// Returns a value that changes whenever the source's output changes, such as a hash of its
+    /// parameters.
+    ///
+    /// Chunks of sources without a fingerprint are never persisted by the
+    /// [`ChunkDiskCache`](super::disk_cache::ChunkDiskCache).
+    fn fingerprint(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Layered gradient noise (fractal Brownian motion) sampled on the unit sphere.
+///
+/// `frequency` is expressed in cycles per unit-sphere radius, so the same configuration produces
+/// the same continents regardless of the body's size. `amplitude` is the maximum elevation in
+/// meters; the output is in `[-amplitude, amplitude]`.
+#[derive(Reflect, Copy, Clone, Debug, PartialEq)]
+pub struct FractalNoise {
+    pub seed: u32,
+    pub octaves: u32,
+    pub frequency: Scalar,
+    pub amplitude: Scalar,
+    pub lacunarity: Scalar,
+    pub persistence: Scalar,
+}
+
+impl FractalNoise {
+    pub const FLAT: Self = Self {
+        seed: 0,
+        octaves: 0,
+        frequency: 1.0,
+        amplitude: 0.0,
+        lacunarity: 2.0,
+        persistence: 0.5,
+    };
+
+    pub const EARTH: Self = Self {
+        seed: 0x5EED_0001,
+        octaves: 16,
+        frequency: 2.0,
+        amplitude: 8_000.0,
+        lacunarity: 2.0,
+        persistence: 0.5,
+    };
+
+    pub const MOON: Self = Self {
+        seed: 0x5EED_0002,
+        octaves: 12,
+        frequency: 3.0,
+        amplitude: 4_000.0,
+        lacunarity: 2.0,
+        persistence: 0.45,
+    };
+
+    /// Returns the sum of the octave weights, used to normalize the output to `[-1, 1]`.
+    fn total_weight(&self) -> Scalar {
+        (0..self.octaves)
+            .map(|octave| self.persistence.powi(octave as i32))
+            .sum()
+    }
+}
+
+impl Default for FractalNoise {
+    fn default() -> Self {
+        Self::EARTH
+    }
+}
+
+impl HeightSource for FractalNoise {
+    fn height(&self, direction: Vector) -> Scalar {
+        if self.octaves == 0 || self.amplitude == 0.0 {
+            return 0.0;
+        }
+
+        let mut frequency = self.frequency;
+        let mut weight = 1.0;
+        let mut sum = 0.0;
+        for octave in 0..self.octaves {
+            let seed = self.seed.wrapping_add(octave.wrapping_mul(0x9E37_79B9));
+            sum += gradient_noise(direction * frequency, seed) * weight;
+            frequency *= self.lacunarity;
+            weight *= self.persistence;
+        }
+
+        sum / self.total_weight() * self.amplitude
+    }
+
+    fn max_variation(&self, distance: Scalar) -> Option<Scalar> {
+        if self.octaves == 0 || self.amplitude == 0.0 {
+            return Some(0.0);
+        }
+
+        // Each octave changes by at most its slope times the distance, and never by more than
+        // its full range.
+        let mut frequency = self.frequency;
+        let mut weight = 1.0;
+        let mut sum = 0.0;
+        for _ in 0..self.octaves {
+            sum += (GRADIENT_NOISE_SLOPE * frequency * distance).min(2.0) * weight;
+            frequency *= self.lacunarity;
+            weight *= self.persistence;
+        }
+
+        Some(sum / self.total_weight() * self.amplitude)
+    }
+
+    fn fingerprint(&self) -> Option<u64> {
+        let mut hasher = FingerprintHasher::default();
+        hasher.write_u32(self.seed);
+        hasher.write_u32(self.octaves);
+        for parameter in [
+            self.frequency,
+            self.amplitude,
+            self.lacunarity,
+            self.persistence,
+        ] {
+            hasher.write_u64(parameter.to_bits() as u64);
+        }
+        Some(hasher.finish())
+    }
+}
+
+/// A shareable, type-erased [`HeightSource`] attached to a [`Body`](super::Body).
+///
+/// Insert this component alongside a `Body` to override the body's built-in [`FractalNoise`].
+#[derive(Component, Clone)]
+pub struct Heightfield(Arc<dyn HeightSource>);
+
+impl Heightfield {
+    pub fn new(source: impl HeightSource) -> Self {
+        Self(Arc::new(source))
+    }
+}
+
+impl Default for Heightfield {
+    fn default() -> Self {
+        Self::new(FractalNoise::FLAT)
+    }
+}
+
+impl Deref for Heightfield {
+    type Target = dyn HeightSource;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+impl std::fmt::Debug for Heightfield {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Heightfield").finish_non_exhaustive()
+    }
+}
+
+impl From<FractalNoise> for Heightfield {
+    fn from(value: FractalNoise) -> Self {
+        Self::new(value)
+    }
+}
+
+/// Upper bound on the gradient magnitude of [`gradient_noise`].
+const GRADIENT_NOISE_SLOPE: Scalar = 4.0;
+
+/// Hashes a lattice point into a pseudo-random `u32`.
+#[inline]
+fn hash_lattice(x: i64, y: i64, z: i64, seed: u32) -> u32 {
+    let mut h = seed as u64;
+    h ^= (x as u64).wrapping_mul(0x8DA6_B343);
+    h ^= (y as u64).wrapping_mul(0xD816_3841);
+    h ^= (z as u64).wrapping_mul(0xCB1A_B31F);
+    h = (h ^ (h >> 33)).wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    h = (h ^ (h >> 33)).wrapping_mul(0xC4CE_B9FE_1A85_EC53);
+    (h ^ (h >> 33)) as u32
+}
+
+/// Dot product of the offset with one of the twelve cube-edge gradients selected by `hash`.
+#[inline]
+fn gradient_dot(hash: u32, x: Scalar, y: Scalar, z: Scalar) -> Scalar {
+    match hash % 12 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+#[inline]
+fn fade(t: Scalar) -> Scalar {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+#[inline]
+fn lerp(a: Scalar, b: Scalar, t: Scalar) -> Scalar {
+    a + (b - a) * t
+}
+
+/// Three dimensional gradient noise in approximately `[-1, 1]`.
+pub fn gradient_noise(point: Vector, seed: u32) -> Scalar {
+    let cell = point.floor();
+    let [x0, y0, z0] = cell.to_array().map(|v| v as i64);
+    let [fx, fy, fz] = (point - cell).to_array();
+    let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+
+    let corner = |dx: i64, dy: i64, dz: i64| {
+        gradient_dot(
+            hash_lattice(x0 + dx, y0 + dy, z0 + dz, seed),
+            fx - dx as Scalar,
+            fy - dy as Scalar,
+            fz - dz as Scalar,
+        )
+    };
+
+    let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), u);
+    let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), u);
+    let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), u);
+    let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), u);
+
+    lerp(lerp(x00, x10, v), lerp(x01, x11, v), w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flat_noise_is_zero() {
+        let direction = Vector::new(1.0, 2.0, 3.0).normalize();
+        assert_eq!(FractalNoise::FLAT.height(direction), 0.0);
+    }
+
+    #[test]
+    fn test_fractal_noise_is_deterministic() {
+        let noise = FractalNoise::EARTH;
+        let direction = Vector::new(-0.3, 0.8, 0.2).normalize();
+        assert_eq!(noise.height(direction), noise.height(direction));
+
+        let other = FractalNoise {
+            seed: noise.seed + 1,
+            ..noise
+        };
+        assert_ne!(noise.height(direction), other.height(direction));
+    }
+
+    #[test]
+    fn test_fractal_noise_within_amplitude() {
+        let noise = FractalNoise::MOON;
+        for i in 0..1000 {
+            let t = i as Scalar * 0.37;
+            let direction = Vector::new(t.sin(), (t * 1.3).cos(), (t * 0.7).sin()).normalize();
+            let height = noise.height(direction);
+            assert!(
+                height.abs() <= noise.amplitude * 1.1,
+                "{height} out of range"
+            );
+        }
+    }
+
+    #[test]
+    fn test_max_variation_bounds_nearby_samples() {
+        let noise = FractalNoise::EARTH;
+        let distance = 1e-3;
+        let bound = noise
+            .max_variation(distance)
+            .expect("expected fractal noise to bound its variation");
+        assert!(bound < noise.amplitude);
+        for i in 0..1000 {
+            let t = i as Scalar * 0.37;
+            let direction = Vector::new(t.sin(), (t * 1.3).cos(), (t * 0.7).sin()).normalize();
+            let offset = direction.any_orthonormal_vector() * distance;
+            let other = (direction + offset).normalize();
+            let change = (noise.height(direction) - noise.height(other)).abs();
+            assert!(change <= bound, "{change} exceeds {bound}");
+        }
+    }
+}
@@ -75,13 +75,61 @@ pub fn unit_sphere_to_cube(pos: Vector, iterations: usize) -> Vector {
     Vector::new(cube_x, cube_y, cube_z)
 }
 
-pub fn spherical_uv(normal: Vector) -> Vector2 {
-    debug_assert!(normal.is_normalized(), "normal vector passed to spherical_uv must be normalized");
-    let phi = normal.z.atan2(normal.x); // Azimuth
-    let theta = (normal.y).acos(); // Elevation
-    let u = phi / (2.0 * PI) + 0.5;
-    let v = theta / PI;
-    Vector2::new(u, v)
+/// Maps a point in a face's local coordinates to the surface of the unit cube.
+///
+/// Coordinates in `[-1, 1]` lie on the face itself. Coordinates slightly outside that range are
+/// folded over the cube edge onto the adjacent face, so samples taken just past a face border land
+/// exactly where the neighbouring face would place them.
+pub fn face_to_cube(axis: Axis, face_pos: Vector2) -> Vector {
+    let (axis_normal, local_x, local_y) = AXIS_COORDINATE_FRAMES[&axis];
+    let (u, v) = (face_pos.x, face_pos.y);
+    if u.abs() > 1.0 {
+        axis_normal * (2.0 - u.abs()) + local_x * u.signum() + local_y * v.clamp(-1.0, 1.0)
+    } else if v.abs() > 1.0 {
+        axis_normal * (2.0 - v.abs()) + local_x * u + local_y * v.signum()
+    } else {
+        axis_normal + local_x * u + local_y * v
+    }
+}
+
+/// Projects a point on (or near) the unit cube onto the face it lies on, returning that face and
+/// the point's coordinates in the face's local frame.
+pub fn cube_to_face(pos: Vector) -> (Axis, Vector2) {
+    let abs = pos.abs();
+    let axis = if abs.x >= abs.y && abs.x >= abs.z {
+        if pos.x >= 0.0 {
+            Axis::X
+        } else {
+            Axis::NegX
+        }
+    } else if abs.y >= abs.z {
+        if pos.y >= 0.0 {
+            Axis::Y
+        } else {
+            Axis::NegY
+        }
+    } else if pos.z >= 0.0 {
+        Axis::Z
+    } else {
+        Axis::NegZ
+    };
+    let (axis_normal, local_x, local_y) = AXIS_COORDINATE_FRAMES[&axis];
+    let on_face = pos / pos.dot(axis_normal);
+    (
+        axis,
+        Vector2::new(on_face.dot(local_x), on_face.dot(local_y)),
+    )
+}
+
+/// Maps a point in a face's local coordinates in `[-1, 1]` to the face's UVs in `[0, 1]`.
+///
+/// U follows the face's local x axis and V its local y axis, matching the chunk tangents. Every
+/// face gets the whole texture once, with no seams inside a face and no pinching anywhere, but
+/// neighbouring faces meet with different frames, so the texture is flipped or rotated across
+/// the cube's edges. Tiling textures should use a triplanar projection instead, see
+/// [`Geomorph`](super::material::Geomorph).
+pub fn face_uv(face_pos: Vector2) -> Vector2 {
+    (face_pos + 1.0) / 2.0
 }
 
 pub fn center_on_sphere(axis: Axis, radius: Scalar, bounds: &Rectangle) -> Vector {
@@ -1,16 +1,717 @@
 use bevy::{
     ecs::{component::ComponentId, world::DeferredWorld},
+    pbr::{ExtendedMaterial, MaterialExtension, MaterialExtensionKey, MaterialExtensionPipeline},
     prelude::*,
+    render::{
+        mesh::MeshVertexBufferLayoutRef,
+        render_resource::{
+            AsBindGroup, RenderPipelineDescriptor, ShaderRef, ShaderType,
+            SpecializedMeshPipelineError,
+        },
+        storage::ShaderStorageBuffer,
+    },
+    utils::HashMap,
 };
 
+use super::biome::{Biomes, MAX_BIOMES};
 use super::body::Chunk;
+use super::cube_tree::Axis;
+use super::helpers::AXIS_COORDINATE_FRAMES;
+use super::mesh::{
+    ATTRIBUTE_ALTITUDE_SLOPE, ATTRIBUTE_BIOME_WEIGHTS, ATTRIBUTE_CHUNK_SLOT, ATTRIBUTE_HEIGHT,
+    ATTRIBUTE_MORPH, ATTRIBUTE_MORPH_NORMAL, ATTRIBUTE_OCEAN_DEPTH, ATTRIBUTE_OCTAHEDRAL_NORMAL,
+    ATTRIBUTE_PACKED_BIOME_WEIGHTS, ATTRIBUTE_TEXTURE_POSITION, TEXTURE_PERIOD,
+};
+use crate::math::Rectangle;
+use avian3d::math::{AsF32, Scalar, Vector};
+use std::ops::Range;
+
+/// How many [`MorphObservers`] the terrain materials geomorph around.
+pub const MAX_MORPH_OBSERVERS: usize = 8;
+
+/// An observer that chunks geomorph around, as the [`CubeTree`](super::cube_tree::CubeTree) of
+/// its body measures distances from it.
+#[derive(ShaderType, Reflect, Copy, Clone, Debug, Default, PartialEq)]
+pub struct MorphObserver {
+    /// The observer's world position.
+    pub position: Vec3,
+    pub radius: f32,
+    pub weight: f32,
+}
+
+/// The observers the terrain materials geomorph around, kept up to date from the observers of
+/// every [`CubeTree`](super::cube_tree::CubeTree).
+///
+/// A vertex morphs by its distance to the nearest observer, less the observer's radius and
+/// divided by its weight, which is the distance the tree splits and merges chunks at. Observers
+/// beyond [`MAX_MORPH_OBSERVERS`] are ignored.
+#[derive(ShaderType, Reflect, Copy, Clone, Debug, Default, PartialEq)]
+pub struct MorphObservers {
+    pub count: u32,
+    pub observers: [MorphObserver; MAX_MORPH_OBSERVERS],
+}
+
+impl MorphObservers {
+    pub fn new(observers: impl IntoIterator<Item = MorphObserver>) -> Self {
+        let mut morph_observers = Self::default();
+        for observer in observers.into_iter().take(MAX_MORPH_OBSERVERS) {
+            morph_observers.observers[morph_observers.count as usize] = observer;
+            morph_observers.count += 1;
+        }
+        morph_observers
+    }
+}
+
+/// Adds the geomorphing attributes to a prepass of a terrain material, whose vertex shader is
+/// `shaders/terrain_prepass.wgsl`.
+fn specialize_morph_prepass(
+    descriptor: &mut RenderPipelineDescriptor,
+    layout: &MeshVertexBufferLayoutRef,
+) -> Result<(), SpecializedMeshPipelineError> {
+    let mut attributes = vec![
+        Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+        ATTRIBUTE_MORPH.at_shader_location(8),
+        ATTRIBUTE_MORPH_NORMAL.at_shader_location(12),
+    ];
+    let defs = &descriptor.vertex.shader_defs;
+    if defs.contains(&"VERTEX_UVS_A".into()) {
+        attributes.push(Mesh::ATTRIBUTE_UV_0.at_shader_location(1));
+    }
+    if defs.contains(&"NORMAL_PREPASS_OR_DEFERRED_PREPASS".into()) {
+        attributes.push(Mesh::ATTRIBUTE_NORMAL.at_shader_location(3));
+        if defs.contains(&"VERTEX_TANGENTS".into()) {
+            attributes.push(Mesh::ATTRIBUTE_TANGENT.at_shader_location(4));
+        }
+    }
+    descriptor.vertex.buffers = vec![layout.0.get_layout(&attributes)?];
+    Ok(())
+}
+
+/// The default terrain material: PBR shading over chunks that geomorph toward their parent LOD.
+pub type TerrainStandardMaterial = ExtendedMaterial<StandardMaterial, Geomorph>;
+
+/// Blends chunk vertices and normals toward the parent LOD, see [`ATTRIBUTE_MORPH`] and
+/// [`ATTRIBUTE_MORPH_NORMAL`], so chunks never pop when the tree splits or merges. Prepasses and
+/// shadows morph along with the main pass.
+///
+/// Chunks carry per-face UVs, see [`face_uv`](super::helpers::face_uv), which stretch a texture
+/// over each cube face and flip or rotate it across the cube's edges. With
+/// [`Geomorph::triplanar`], the base color texture is instead projected along the three axes of
+/// the body from [`ATTRIBUTE_TEXTURE_POSITION`], so it tiles uniformly over the whole body and
+/// stays put as the body moves and rotates.
+///
+/// Chunks with [`ATTRIBUTE_BIOME_WEIGHTS`] are tinted by the blend of their `biome_colors`.
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+#[bind_group_data(GeomorphKey)]
+pub struct Geomorph {
+    /// Fraction of a vertex's morph distance at which it starts moving toward the parent LOD.
+    #[uniform(100)]
+    pub morph_start: f32,
+    /// The observers chunks morph around. The terrain plugin keeps these up to date.
+    #[uniform(100)]
+    pub observers: MorphObservers,
+    /// Texture repeats per meter of the triplanar projection, rounded to a whole number of
+    /// repeats per [`TEXTURE_PERIOD`].
+    #[uniform(100)]
+    pub triplanar_scale: f32,
+    /// The colors of the body's [`Biomes`], in their order.
+    #[uniform(100)]
+    pub biome_colors: [LinearRgba; MAX_BIOMES],
+    pub triplanar: bool,
+}
+
+impl Geomorph {
+    /// Projects the base color texture triplanar, repeating it about `scale` times per meter.
+    pub fn with_triplanar(mut self, scale: f32) -> Self {
+        self.triplanar = true;
+        self.triplanar_scale = scale;
+        self
+    }
+
+    /// Shades chunks with the colors of `biomes`.
+    pub fn with_biomes(mut self, biomes: &Biomes) -> Self {
+        self.biome_colors = biomes.colors();
+        self
+    }
+}
+
+impl Default for Geomorph {
+    fn default() -> Self {
+        Self {
+            morph_start: 0.75,
+            observers: MorphObservers::default(),
+            triplanar_scale: 0.01,
+            biome_colors: Biomes::default().colors(),
+            triplanar: false,
+        }
+    }
+}
+
+/// The pipeline variant of a [`Geomorph`] material.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GeomorphKey {
+    triplanar: bool,
+}
+
+impl From<&Geomorph> for GeomorphKey {
+    fn from(geomorph: &Geomorph) -> Self {
+        Self {
+            triplanar: geomorph.triplanar,
+        }
+    }
+}
+
+impl MaterialExtension for Geomorph {
+    fn vertex_shader() -> ShaderRef {
+        "shaders/terrain_geomorph.wgsl".into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        "shaders/terrain_geomorph.wgsl".into()
+    }
+
+    fn prepass_vertex_shader() -> ShaderRef {
+        "shaders/terrain_prepass.wgsl".into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialExtensionPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayoutRef,
+        key: MaterialExtensionKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if descriptor
+            .vertex
+            .shader_defs
+            .contains(&"PREPASS_PIPELINE".into())
+        {
+            return specialize_morph_prepass(descriptor, layout);
+        }
+        let mut fragment_defs = Vec::new();
+        if key.bind_group_data.triplanar {
+            fragment_defs.push("TERRAIN_TRIPLANAR".into());
+        }
+        let mut attributes = vec![
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            Mesh::ATTRIBUTE_NORMAL.at_shader_location(1),
+            Mesh::ATTRIBUTE_UV_0.at_shader_location(2),
+            ATTRIBUTE_MORPH.at_shader_location(8),
+            ATTRIBUTE_TEXTURE_POSITION.at_shader_location(11),
+            ATTRIBUTE_MORPH_NORMAL.at_shader_location(12),
+        ];
+        // Bevy's mesh pipeline has already added `VERTEX_TANGENTS` for meshes with tangents.
+        if layout.0.contains(Mesh::ATTRIBUTE_TANGENT) {
+            attributes.push(Mesh::ATTRIBUTE_TANGENT.at_shader_location(4));
+        }
+        // Biome colors reach the fragment shader as vertex colors.
+        if layout.0.contains(ATTRIBUTE_BIOME_WEIGHTS) {
+            attributes.push(ATTRIBUTE_BIOME_WEIGHTS.at_shader_location(9));
+            descriptor.vertex.shader_defs.push("TERRAIN_BIOMES".into());
+            descriptor.vertex.shader_defs.push("VERTEX_COLORS".into());
+            fragment_defs.push("VERTEX_COLORS".into());
+        }
+        descriptor.vertex.buffers = vec![layout.0.get_layout(&attributes)?];
+        if let Some(fragment) = descriptor.fragment.as_mut() {
+            fragment.shader_defs.extend(fragment_defs);
+        }
+        Ok(())
+    }
+}
+
+/// The material of chunks built with [`VertexMode::Compact`](super::mesh::VertexMode::Compact).
+pub type TerrainCompactMaterial = ExtendedMaterial<StandardMaterial, CompactChunk>;
+
+/// Rebuilds the positions and UVs of compact chunks from their [`ATTRIBUTE_HEIGHT`]s and
+/// [`ATTRIBUTE_OCTAHEDRAL_NORMAL`]s.
+///
+/// Every body has one compact material, see [`CompactChunkSlots`]. Vertices read where their
+/// chunk lies from the [`CompactChunkRecord`] at their [`ATTRIBUTE_CHUNK_SLOT`].
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+pub struct CompactChunk {
+    /// The colors of the body's [`Biomes`], in their order.
+    #[uniform(100)]
+    pub biome_colors: [LinearRgba; MAX_BIOMES],
+    /// The [`CompactChunkRecord`]s of the body's chunks, indexed by slot.
+    #[storage(101, read_only)]
+    pub chunks: Handle<ShaderStorageBuffer>,
+}
+
+impl CompactChunk {
+    pub fn new(chunks: Handle<ShaderStorageBuffer>) -> Self {
+        Self {
+            biome_colors: [LinearRgba::WHITE; MAX_BIOMES],
+            chunks,
+        }
+    }
+
+    /// Shades chunks with the colors of `biomes`.
+    pub fn with_biomes(mut self, biomes: &Biomes) -> Self {
+        self.biome_colors = biomes.colors();
+        self
+    }
+}
+
+/// Where a compact chunk lies on its body, as read by `shaders/terrain_compact.wgsl`.
+#[derive(ShaderType, Reflect, Copy, Clone, Debug, Default, PartialEq)]
+pub struct CompactChunkRecord {
+    /// The unit normal of the chunk's cube face.
+    pub face_normal: Vec3,
+    /// Vertices along each side of the chunk, not counting skirts.
+    pub vertex_count: u32,
+    /// The direction of the face's local x axis.
+    pub face_x: Vec3,
+    pub radius: f32,
+    /// The direction of the face's local y axis.
+    pub face_y: Vec3,
+    /// The chunk's minimum and maximum corners, in face coordinates normalized to `[-1, 1]`.
+    pub bounds: Vec4,
+    /// The chunk's center relative to the body, which is the origin of its mesh.
+    pub center: Vec3,
+}
+
+impl CompactChunkRecord {
+    pub fn new(
+        axis: Axis,
+        bounds: &Rectangle,
+        radius: Scalar,
+        center: Vector,
+        vertex_count: usize,
+    ) -> Self {
+        let (face_normal, face_x, face_y) = AXIS_COORDINATE_FRAMES[&axis];
+        let (min, max) = (bounds.min / radius, bounds.max / radius);
+        Self {
+            face_normal: face_normal.f32(),
+            vertex_count: vertex_count as u32,
+            face_x: face_x.f32(),
+            radius: radius as f32,
+            face_y: face_y.f32(),
+            bounds: Vec4::new(min.x as f32, min.y as f32, max.x as f32, max.y as f32),
+            center: center.f32(),
+        }
+    }
+}
+
+/// The compact material of a body, and the slots of its [`CompactChunkRecord`]s.
+///
+/// Slots of despawned chunks are reused by the next chunks. The terrain plugin uploads the
+/// records to the material's storage buffer whenever they change.
+#[derive(Component, Debug)]
+pub struct CompactChunkSlots {
+    pub material: Handle<TerrainCompactMaterial>,
+    records: Vec<CompactChunkRecord>,
+    slots: HashMap<Entity, u32>,
+    free: Vec<u32>,
+}
+
+impl CompactChunkSlots {
+    pub fn new(material: Handle<TerrainCompactMaterial>) -> Self {
+        Self {
+            material,
+            records: Vec::new(),
+            slots: HashMap::default(),
+            free: Vec::new(),
+        }
+    }
+
+    /// Stores the record of `chunk` and returns its slot, which stays the same until the chunk
+    /// is removed.
+    pub fn insert(&mut self, chunk: Entity, record: CompactChunkRecord) -> u32 {
+        let slot = match self.slots.get(&chunk) {
+            Some(&slot) => slot,
+            None => {
+                let slot = self.free.pop().unwrap_or(self.records.len() as u32);
+                self.slots.insert(chunk, slot);
+                slot
+            }
+        };
+        match self.records.get_mut(slot as usize) {
+            Some(stored) => *stored = record,
+            None => self.records.push(record),
+        }
+        slot
+    }
+
+    /// Frees the slot of `chunk`, if it has one.
+    pub fn remove(&mut self, chunk: Entity) {
+        if let Some(slot) = self.slots.remove(&chunk) {
+            self.free.push(slot);
+        }
+    }
+
+    /// Returns the records of every slot, including free ones.
+    pub fn records(&self) -> &[CompactChunkRecord] {
+        &self.records
+    }
+}
+
+impl MaterialExtension for CompactChunk {
+    fn vertex_shader() -> ShaderRef {
+        "shaders/terrain_compact.wgsl".into()
+    }
+
+    fn prepass_vertex_shader() -> ShaderRef {
+        "shaders/terrain_compact.wgsl".into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialExtensionPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayoutRef,
+        _key: MaterialExtensionKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        let mut attributes = vec![
+            ATTRIBUTE_HEIGHT.at_shader_location(0),
+            ATTRIBUTE_OCTAHEDRAL_NORMAL.at_shader_location(1),
+            ATTRIBUTE_CHUNK_SLOT.at_shader_location(3),
+        ];
+        let main_pass = !descriptor
+            .vertex
+            .shader_defs
+            .contains(&"PREPASS_PIPELINE".into());
+        // UVs are computed in the vertex shader, so the main pass can sample textures even
+        // though the mesh has none.
+        let mut defs = vec!["VERTEX_UVS", "VERTEX_UVS_A"];
+        if layout.0.contains(ATTRIBUTE_PACKED_BIOME_WEIGHTS) {
+            attributes.push(ATTRIBUTE_PACKED_BIOME_WEIGHTS.at_shader_location(2));
+            defs.push("VERTEX_COLORS");
+            if main_pass {
+                descriptor.vertex.shader_defs.push("TERRAIN_BIOMES".into());
+            }
+        }
+        descriptor.vertex.buffers = vec![layout.0.get_layout(&attributes)?];
+        if main_pass {
+            for def in defs {
+                descriptor.vertex.shader_defs.push(def.into());
+                if let Some(fragment) = descriptor.fragment.as_mut() {
+                    fragment.shader_defs.push(def.into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How many layers a [`Splat`] material can blend.
+pub const MAX_SPLAT_LAYERS: usize = 8;
+
+/// A terrain material that blends several textured layers, such as rock, grass, snow and sand.
+pub type TerrainSplatMaterial = ExtendedMaterial<StandardMaterial, Splat>;
+
+/// One layer of a [`Splat`] material, and where on the terrain it appears.
+///
+/// A layer's weight at a point is its share of the point's biome weights, faded out over the
+/// blend widths beyond its altitude and slope ranges.
+#[derive(ShaderType, Reflect, Copy, Clone, Debug)]
+pub struct SplatLayer {
+    /// Multiplies the layer's albedo.
+    pub tint: LinearRgba,
+    /// How much the layer covers each of the body's [`Biomes`], in their order.
+    pub biomes: Vec4,
+    /// The lowest and highest altitude, in meters above sea level, the layer covers.
+    pub altitude: Vec2,
+    /// The flattest and steepest slope, in radians, the layer covers.
+    pub slope: Vec2,
+    /// Altitude, in meters, over which the layer fades out beyond its altitude range.
+    pub altitude_blend: f32,
+    /// Slope, in radians, over which the layer fades out beyond its slope range.
+    pub slope_blend: f32,
+}
+
+impl SplatLayer {
+    /// A layer covering every biome, altitude and slope.
+    pub fn new() -> Self {
+        Self {
+            tint: LinearRgba::WHITE,
+            biomes: Vec4::ONE,
+            altitude: Vec2::new(f32::MIN, f32::MAX),
+            slope: Vec2::new(f32::MIN, f32::MAX),
+            altitude_blend: 100.0,
+            slope_blend: 0.1,
+        }
+    }
+
+    pub fn with_tint(mut self, tint: impl Into<LinearRgba>) -> Self {
+        self.tint = tint.into();
+        self
+    }
+
+    pub fn with_biomes(mut self, biomes: Vec4) -> Self {
+        self.biomes = biomes;
+        self
+    }
+
+    pub fn with_altitude(mut self, altitude: Range<f32>, blend: f32) -> Self {
+        self.altitude = Vec2::new(altitude.start, altitude.end);
+        self.altitude_blend = blend;
+        self
+    }
+
+    pub fn with_slope(mut self, slope: Range<f32>, blend: f32) -> Self {
+        self.slope = Vec2::new(slope.start, slope.end);
+        self.slope_blend = blend;
+        self
+    }
+}
+
+impl Default for SplatLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Blends up to [`MAX_SPLAT_LAYERS`] texture layers by the slope, altitude and biome weights of
+/// each chunk vertex, see [`ATTRIBUTE_ALTITUDE_SLOPE`] and [`ATTRIBUTE_BIOME_WEIGHTS`].
+///
+/// Layers are textured from array textures with one array layer per [`SplatLayer`], for example
+/// made with [`Image::reinterpret_stacked_2d_as_array`]. They are projected triplanar along the
+/// body's axes from [`ATTRIBUTE_TEXTURE_POSITION`], so they tile uniformly over the whole body
+/// and stay put as the body moves and rotates, and chunks geomorph as with
+/// [`Geomorph`]. Compact chunks keep their body's compact material and are not splatted.
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+#[bind_group_data(SplatKey)]
+pub struct Splat {
+    /// Fraction of a vertex's morph distance at which it starts moving toward the parent LOD.
+    #[uniform(100)]
+    pub morph_start: f32,
+    /// The observers chunks morph around. The terrain plugin keeps these up to date.
+    #[uniform(100)]
+    pub observers: MorphObservers,
+    /// Texture repeats per meter, rounded to a whole number of repeats per [`TEXTURE_PERIOD`].
+    #[uniform(100)]
+    pub triplanar_scale: f32,
+    #[uniform(100)]
+    layer_count: u32,
+    #[uniform(100)]
+    layers: [SplatLayer; MAX_SPLAT_LAYERS],
+    /// The albedo of every layer.
+    #[texture(101, dimension = "2d_array")]
+    #[sampler(102)]
+    pub albedo: Handle<Image>,
+    /// The tangent-space normal map of every layer.
+    #[texture(103, dimension = "2d_array")]
+    #[sampler(104)]
+    pub normal_map: Option<Handle<Image>>,
+}
+
+impl Splat {
+    pub fn new(albedo: Handle<Image>) -> Self {
+        Self {
+            morph_start: Geomorph::default().morph_start,
+            observers: MorphObservers::default(),
+            triplanar_scale: Geomorph::default().triplanar_scale,
+            layer_count: 0,
+            layers: [SplatLayer::new(); MAX_SPLAT_LAYERS],
+            albedo,
+            normal_map: None,
+        }
+    }
+
+    /// Adds a layer, textured by the next layer of the array textures.
+    pub fn with_layer(mut self, layer: SplatLayer) -> Self {
+        assert!(
+            (self.layer_count as usize) < MAX_SPLAT_LAYERS,
+            "expected at most {MAX_SPLAT_LAYERS} splat layers"
+        );
+        self.layers[self.layer_count as usize] = layer;
+        self.layer_count += 1;
+        self
+    }
+
+    pub fn with_normal_map(mut self, normal_map: Handle<Image>) -> Self {
+        self.normal_map = Some(normal_map);
+        self
+    }
+
+    pub fn with_triplanar_scale(mut self, scale: f32) -> Self {
+        self.triplanar_scale = scale;
+        self
+    }
+
+    pub fn layers(&self) -> &[SplatLayer] {
+        &self.layers[..self.layer_count as usize]
+    }
+}
+
+/// The pipeline variant of a [`Splat`] material.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SplatKey {
+    normal_map: bool,
+}
+
+impl From<&Splat> for SplatKey {
+    fn from(splat: &Splat) -> Self {
+        Self {
+            normal_map: splat.normal_map.is_some(),
+        }
+    }
+}
+
+impl MaterialExtension for Splat {
+    fn vertex_shader() -> ShaderRef {
+        "shaders/terrain_splat.wgsl".into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        "shaders/terrain_splat.wgsl".into()
+    }
+
+    fn prepass_vertex_shader() -> ShaderRef {
+        "shaders/terrain_prepass.wgsl".into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialExtensionPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayoutRef,
+        key: MaterialExtensionKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if descriptor
+            .vertex
+            .shader_defs
+            .contains(&"PREPASS_PIPELINE".into())
+        {
+            return specialize_morph_prepass(descriptor, layout);
+        }
+        let mut attributes = vec![
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            Mesh::ATTRIBUTE_NORMAL.at_shader_location(1),
+            Mesh::ATTRIBUTE_UV_0.at_shader_location(2),
+            ATTRIBUTE_MORPH.at_shader_location(8),
+            ATTRIBUTE_ALTITUDE_SLOPE.at_shader_location(10),
+            ATTRIBUTE_TEXTURE_POSITION.at_shader_location(11),
+            ATTRIBUTE_MORPH_NORMAL.at_shader_location(12),
+        ];
+        if layout.0.contains(Mesh::ATTRIBUTE_TANGENT) {
+            attributes.push(Mesh::ATTRIBUTE_TANGENT.at_shader_location(4));
+        }
+        // Altitudes and slopes reach the fragment shader as the second UV channel, and biome
+        // weights as vertex colors.
+        let mut defs = vec!["VERTEX_UVS_B"];
+        if layout.0.contains(ATTRIBUTE_BIOME_WEIGHTS) {
+            attributes.push(ATTRIBUTE_BIOME_WEIGHTS.at_shader_location(9));
+            defs.extend(["TERRAIN_BIOMES", "VERTEX_COLORS"]);
+        }
+        if key.bind_group_data.normal_map {
+            defs.push("SPLAT_NORMAL_MAP");
+        }
+        descriptor.vertex.buffers = vec![layout.0.get_layout(&attributes)?];
+        for def in defs {
+            descriptor.vertex.shader_defs.push(def.into());
+            if let Some(fragment) = descriptor.fragment.as_mut() {
+                fragment.shader_defs.push(def.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The material of ocean chunks, see [`OceanMeshBuilder`](super::ocean::OceanMeshBuilder).
+pub type TerrainOceanMaterial = ExtendedMaterial<StandardMaterial, Water>;
+
+/// Colors ocean chunks by the depth of the terrain below them, see [`ATTRIBUTE_OCEAN_DEPTH`].
+///
+/// Water fades from `shallow_color` at the shore to `deep_color`, which it has mostly reached
+/// `depth_scale` meters down. The alpha of the colors decides how much of the seabed shows
+/// through, so the base material should blend. Chunks geomorph as with [`Geomorph`].
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+pub struct Water {
+    /// Fraction of a vertex's morph distance at which it starts moving toward the parent LOD.
+    #[uniform(100)]
+    pub morph_start: f32,
+    /// The observers chunks morph around. The terrain plugin keeps these up to date.
+    #[uniform(100)]
+    pub observers: MorphObservers,
+    #[uniform(100)]
+    pub shallow_color: LinearRgba,
+    #[uniform(100)]
+    pub deep_color: LinearRgba,
+    #[uniform(100)]
+    pub depth_scale: f32,
+}
+
+impl Water {
+    pub fn with_colors(
+        mut self,
+        shallow_color: impl Into<LinearRgba>,
+        deep_color: impl Into<LinearRgba>,
+    ) -> Self {
+        self.shallow_color = shallow_color.into();
+        self.deep_color = deep_color.into();
+        self
+    }
+
+    pub fn with_depth_scale(mut self, depth_scale: f32) -> Self {
+        self.depth_scale = depth_scale;
+        self
+    }
+}
+
+impl Default for Water {
+    fn default() -> Self {
+        Self {
+            morph_start: Geomorph::default().morph_start,
+            observers: MorphObservers::default(),
+            shallow_color: LinearRgba::new(0.1, 0.55, 0.6, 0.35),
+            deep_color: LinearRgba::new(0.0, 0.04, 0.12, 0.95),
+            depth_scale: 50.0,
+        }
+    }
+}
+
+impl MaterialExtension for Water {
+    fn vertex_shader() -> ShaderRef {
+        "shaders/terrain_ocean.wgsl".into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        "shaders/terrain_ocean.wgsl".into()
+    }
+
+    fn prepass_vertex_shader() -> ShaderRef {
+        "shaders/terrain_prepass.wgsl".into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialExtensionPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayoutRef,
+        _key: MaterialExtensionKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if descriptor
+            .vertex
+            .shader_defs
+            .contains(&"PREPASS_PIPELINE".into())
+        {
+            return specialize_morph_prepass(descriptor, layout);
+        }
+        let mut attributes = vec![
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            Mesh::ATTRIBUTE_NORMAL.at_shader_location(1),
+            Mesh::ATTRIBUTE_UV_0.at_shader_location(2),
+            ATTRIBUTE_MORPH.at_shader_location(8),
+            ATTRIBUTE_OCEAN_DEPTH.at_shader_location(11),
+            ATTRIBUTE_MORPH_NORMAL.at_shader_location(12),
+        ];
+        if layout.0.contains(Mesh::ATTRIBUTE_TANGENT) {
+            attributes.push(Mesh::ATTRIBUTE_TANGENT.at_shader_location(4));
+        }
+        descriptor.vertex.buffers = vec![layout.0.get_layout(&attributes)?];
+        // Depths reach the fragment shader as the second UV channel.
+        descriptor.vertex.shader_defs.push("VERTEX_UVS_B".into());
+        if let Some(fragment) = descriptor.fragment.as_mut() {
+            fragment.shader_defs.push("VERTEX_UVS_B".into());
+        }
+        Ok(())
+    }
+}
 
 #[cfg(debug_assertions)]
 use crate::materials::debug::{DebugNormalsMaterial, DebugUVsMaterial};
 
 #[derive(Resource, Clone, Debug)]
 pub struct TerrainMaterials {
-    pub standard: Handle<StandardMaterial>,
+    pub standard: Handle<TerrainStandardMaterial>,
+    pub ocean: Handle<TerrainOceanMaterial>,
     #[cfg(debug_assertions)]
     pub debug_normals: Handle<DebugNormalsMaterial>,
     #[cfg(debug_assertions)]
@@ -20,11 +721,22 @@ pub struct TerrainMaterials {
 impl FromWorld for TerrainMaterials {
     fn from_world(world: &mut World) -> Self {
         let standard_handle = world
-            .get_resource_mut::<Assets<StandardMaterial>>()
-            .expect("Expected Assets<StandardMaterial> to exist")
-            .add(StandardMaterial {
-                base_color: Color::linear_rgb(0.12, 0.64, 0.14),
-                ..Default::default()
+            .get_resource_mut::<Assets<TerrainStandardMaterial>>()
+            .expect("Expected Assets<TerrainStandardMaterial> to exist")
+            .add(TerrainStandardMaterial {
+                base: StandardMaterial::default(),
+                extension: Geomorph::default(),
+            });
+        let ocean_handle = world
+            .get_resource_mut::<Assets<TerrainOceanMaterial>>()
+            .expect("Expected Assets<TerrainOceanMaterial> to exist")
+            .add(TerrainOceanMaterial {
+                base: StandardMaterial {
+                    alpha_mode: AlphaMode::Blend,
+                    perceptual_roughness: 0.08,
+                    ..default()
+                },
+                extension: Water::default(),
             });
 
         #[cfg(debug_assertions)]
@@ -41,6 +753,7 @@ impl FromWorld for TerrainMaterials {
 
         Self {
             standard: standard_handle,
+            ocean: ocean_handle,
             #[cfg(debug_assertions)]
             debug_normals: debug_normals_handle,
             #[cfg(debug_assertions)]
@@ -49,24 +762,45 @@ impl FromWorld for TerrainMaterials {
     }
 }
 
-#[cfg(debug_assertions)]
+/// The material of a body's chunks.
 #[derive(Component, Clone, Eq, PartialEq, Debug)]
 #[component(on_insert = on_insert_terrain_material)]
 pub enum TerrainMaterial {
+    #[cfg(debug_assertions)]
     DebugNormals(Handle<DebugNormalsMaterial>),
+    #[cfg(debug_assertions)]
     DebugUVs(Handle<DebugUVsMaterial>),
-    Standard(Handle<StandardMaterial>),
+    Standard(Handle<TerrainStandardMaterial>),
+    Splat(Handle<TerrainSplatMaterial>),
 }
 
-#[cfg(not(debug_assertions))]
-#[derive(Component, Clone, Eq, PartialEq, Debug)]
-#[component(on_insert = on_insert_terrain_material)]
-pub struct TerrainMaterial(pub Handle<StandardMaterial>);
-
-#[cfg(not(debug_assertions))]
 impl TerrainMaterial {
-    pub fn handle(&self) -> Handle<StandardMaterial> {
-        self.0.clone()
+    /// Returns the standard material, if the body uses one.
+    pub fn standard(&self) -> Option<&Handle<TerrainStandardMaterial>> {
+        match self {
+            TerrainMaterial::Standard(handle) => Some(handle),
+            _ => None,
+        }
+    }
+
+    /// Replaces whichever terrain material a chunk has with this one.
+    pub fn apply(&self, chunk: &mut EntityCommands) {
+        chunk
+            .remove::<MeshMaterial3d<TerrainStandardMaterial>>()
+            .remove::<MeshMaterial3d<TerrainSplatMaterial>>();
+        #[cfg(debug_assertions)]
+        chunk
+            .remove::<MeshMaterial3d<DebugNormalsMaterial>>()
+            .remove::<MeshMaterial3d<DebugUVsMaterial>>();
+
+        match self {
+            #[cfg(debug_assertions)]
+            TerrainMaterial::DebugNormals(handle) => chunk.insert(MeshMaterial3d(handle.clone())),
+            #[cfg(debug_assertions)]
+            TerrainMaterial::DebugUVs(handle) => chunk.insert(MeshMaterial3d(handle.clone())),
+            TerrainMaterial::Standard(handle) => chunk.insert(MeshMaterial3d(handle.clone())),
+            TerrainMaterial::Splat(handle) => chunk.insert(MeshMaterial3d(handle.clone())),
+        };
     }
 }
 
@@ -76,43 +810,73 @@ fn on_insert_terrain_material(mut world: DeferredWorld, entity: Entity, _id: Com
         .expect("expected entity to have TerrainMaterial component")
         .clone();
 
+    // Compact chunks shade like the body's standard material.
+    let compact_material = world
+        .get::<CompactChunkSlots>(entity)
+        .map(|slots| slots.material.clone());
+    let base = terrain_material
+        .standard()
+        .and_then(|handle| {
+            world
+                .resource::<Assets<TerrainStandardMaterial>>()
+                .get(handle)
+        })
+        .map(|material| material.base.clone());
+    if let (Some(compact_material), Some(base)) = (compact_material, base) {
+        if let Some(material) = world
+            .resource_mut::<Assets<TerrainCompactMaterial>>()
+            .get_mut(&compact_material)
+        {
+            material.base = base;
+        }
+    }
+
     let Some(children) = world.get::<Children>(entity) else {
         return;
     };
     let children: Vec<Entity> = children.iter().copied().collect();
 
     for &child_entity in children.iter() {
+        // Compact chunks keep the body's compact material, which places their vertices.
+        if world
+            .entity(child_entity)
+            .contains::<MeshMaterial3d<TerrainCompactMaterial>>()
+        {
+            continue;
+        }
         if world.entity(child_entity).contains::<Chunk>() {
-            #[cfg(debug_assertions)]
-            match terrain_material.clone() {
-                TerrainMaterial::Standard(handle) => world
-                    .commands()
-                    .entity(child_entity)
-                    .remove::<MeshMaterial3d<DebugNormalsMaterial>>()
-                    .remove::<MeshMaterial3d<DebugUVsMaterial>>()
-                    .insert(MeshMaterial3d::<StandardMaterial>(handle.clone())),
-
-                TerrainMaterial::DebugNormals(handle) => world
-                    .commands()
-                    .entity(child_entity)
-                    .remove::<MeshMaterial3d<StandardMaterial>>()
-                    .remove::<MeshMaterial3d<DebugUVsMaterial>>()
-                    .insert(MeshMaterial3d::<DebugNormalsMaterial>(handle.clone())),
-
-                TerrainMaterial::DebugUVs(handle) => world
-                    .commands()
-                    .entity(child_entity)
-                    .remove::<MeshMaterial3d<StandardMaterial>>()
-                    .remove::<MeshMaterial3d<DebugNormalsMaterial>>()
-                    .insert(MeshMaterial3d::<DebugUVsMaterial>(handle.clone())),
-            };
-            #[cfg(not(debug_assertions))]
-            world
-                .commands()
-                .entity(child_entity)
-                .insert(MeshMaterial3d::<StandardMaterial>(
-                    terrain_material.handle(),
-                ));
+            terrain_material.apply(&mut world.commands().entity(child_entity));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(radius: f32) -> CompactChunkRecord {
+        CompactChunkRecord {
+            radius,
+            ..default()
         }
     }
+
+    #[test]
+    fn test_compact_chunk_slots_are_reused() {
+        let (a, b, c) = (
+            Entity::from_raw(1),
+            Entity::from_raw(2),
+            Entity::from_raw(3),
+        );
+        let mut slots = CompactChunkSlots::new(Handle::default());
+        assert_eq!(slots.insert(a, record(1.0)), 0);
+        assert_eq!(slots.insert(b, record(2.0)), 1);
+        // Rebuilding a chunk keeps its slot.
+        assert_eq!(slots.insert(a, record(3.0)), 0);
+        assert_eq!(slots.records(), &[record(3.0), record(2.0)]);
+
+        slots.remove(a);
+        assert_eq!(slots.insert(c, record(4.0)), 0);
+        assert_eq!(slots.records(), &[record(4.0), record(2.0)]);
+    }
 }
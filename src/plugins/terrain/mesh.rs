@@ -1,146 +1,1233 @@
 use super::{
-    cube_tree::Axis,
-    helpers::{spherical_uv, unit_cube_to_sphere, AXIS_COORDINATE_FRAMES},
+    biome::{Biomes, MAX_BIOMES},
+    cube_tree::{Axis, Edge, EdgeLods},
+    disk_cache::FingerprintHasher,
+    height::Heightfield,
+    helpers::{face_to_cube, face_uv, unit_cube_to_sphere, AXIS_COORDINATE_FRAMES},
 };
+use crate::constants::terrain::CHUNK_SUBDIVISIONS;
 use crate::math::quad_tree::QuadTreeNode;
 use crate::math::Rectangle;
 use crate::plugins::terrain::cube_tree::{ChunkData, CubeTreeNode};
 use avian3d::math::{Scalar, Vector, Vector2};
+use bevy::utils::HashMap;
 use bevy::{
     asset::RenderAssetUsages,
     prelude::*,
-    render::mesh::{Indices, PrimitiveTopology},
+    render::mesh::{Indices, MeshVertexAttribute, PrimitiveTopology, VertexAttributeValues},
+    render::render_resource::VertexFormat,
 };
+use lazy_static::lazy_static;
+use std::hash::Hasher;
+use std::sync::Mutex;
 
-#[derive(Clone, Copy, Debug)]
-pub struct ChunkMeshBuilder<const SUBDIVISIONS: usize>
-where
-    [(); (SUBDIVISIONS + 2).pow(2)]:,
-    [(); (SUBDIVISIONS + 1).pow(2) * 6]:,
-{
+/// Geomorphing data of a chunk vertex. `xyz` is the vertex position in the parent LOD, relative
+/// to the chunk's center, and `w` is the distance from the nearest observer, as the
+/// [`CubeTree`](super::cube_tree::CubeTree) measures it, at which the vertex has fully morphed
+/// to it, as the chunk is about to merge into its parent.
+pub const ATTRIBUTE_MORPH: MeshVertexAttribute =
+    MeshVertexAttribute::new("Morph", 2_817_400_213, VertexFormat::Float32x4);
+
+/// Elevation of a compact chunk vertex above the body's radius, see [`VertexMode::Compact`].
+pub const ATTRIBUTE_HEIGHT: MeshVertexAttribute =
+    MeshVertexAttribute::new("Height", 2_817_400_214, VertexFormat::Float32);
+
+/// Octahedral-encoded normal of a compact chunk vertex, see [`VertexMode::Compact`].
+pub const ATTRIBUTE_OCTAHEDRAL_NORMAL: MeshVertexAttribute =
+    MeshVertexAttribute::new("OctahedralNormal", 2_817_400_215, VertexFormat::Snorm16x2);
+
+/// How much a chunk vertex belongs to each of its body's [`Biomes`], in their order.
+pub const ATTRIBUTE_BIOME_WEIGHTS: MeshVertexAttribute =
+    MeshVertexAttribute::new("BiomeWeights", 2_817_400_216, VertexFormat::Float32x4);
+
+/// [`ATTRIBUTE_BIOME_WEIGHTS`] of a compact chunk vertex, see [`VertexMode::Compact`].
+pub const ATTRIBUTE_PACKED_BIOME_WEIGHTS: MeshVertexAttribute =
+    MeshVertexAttribute::new("PackedBiomeWeights", 2_817_400_217, VertexFormat::Unorm8x4);
+
+/// Altitude above sea level, in meters, and slope, in radians, of a chunk vertex, for materials
+/// that texture the terrain by them.
+pub const ATTRIBUTE_ALTITUDE_SLOPE: MeshVertexAttribute =
+    MeshVertexAttribute::new("AltitudeSlope", 2_817_400_218, VertexFormat::Float32x2);
+
+/// Depth of the terrain below an ocean chunk vertex, in meters, see
+/// [`OceanMeshBuilder`](super::ocean::OceanMeshBuilder).
+pub const ATTRIBUTE_OCEAN_DEPTH: MeshVertexAttribute =
+    MeshVertexAttribute::new("OceanDepth", 2_817_400_219, VertexFormat::Float32);
+
+/// Position of a chunk vertex relative to its body, with the chunk's center wrapped to
+/// [`TEXTURE_PERIOD`], for materials that project textures in the body's space.
+///
+/// The position follows the body as it moves and rotates, and stays small enough for `f32`
+/// anywhere on it. Chunks are offset by whole periods, so textures that repeat a whole number
+/// of times per period continue across chunk borders.
+pub const ATTRIBUTE_TEXTURE_POSITION: MeshVertexAttribute =
+    MeshVertexAttribute::new("TexturePosition", 2_817_400_220, VertexFormat::Float32x3);
+
+/// Normal of a chunk vertex in the parent LOD, which the normal blends toward along with
+/// [`ATTRIBUTE_MORPH`].
+pub const ATTRIBUTE_MORPH_NORMAL: MeshVertexAttribute =
+    MeshVertexAttribute::new("MorphNormal", 2_817_400_221, VertexFormat::Float32x3);
+
+/// Slot of a compact chunk's record in its body's
+/// [`CompactChunkSlots`](super::material::CompactChunkSlots), the same for all of the chunk's
+/// vertices, see [`VertexMode::Compact`].
+pub const ATTRIBUTE_CHUNK_SLOT: MeshVertexAttribute =
+    MeshVertexAttribute::new("ChunkSlot", 2_817_400_222, VertexFormat::Uint32);
+
+/// The period, in meters, that [`ATTRIBUTE_TEXTURE_POSITION`] wraps chunk centers to.
+pub const TEXTURE_PERIOD: Scalar = 4096.0;
+
+/// Which attributes chunk meshes store per vertex.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum VertexMode {
+    /// Positions, normals, tangents, UVs, geomorph targets and normals, altitudes, slopes and
+    /// texture positions, 96 bytes per vertex, and 16 bytes of biome weights.
+    #[default]
+    Full,
+    /// Only an [`ATTRIBUTE_HEIGHT`], an [`ATTRIBUTE_OCTAHEDRAL_NORMAL`] and an
+    /// [`ATTRIBUTE_CHUNK_SLOT`], 12 bytes per vertex, and 4 bytes of
+    /// [`ATTRIBUTE_PACKED_BIOME_WEIGHTS`].
+    ///
+    /// The vertex shader of the [`TerrainCompactMaterial`](super::material::TerrainCompactMaterial)
+    /// rebuilds positions and UVs from the chunk's face, bounds and the body's radius, which it
+    /// reads from the chunk's slot. Compact chunks do not geomorph, have no tangents for normal
+    /// maps and are reconstructed in `f32`, so they suit distant or numerous chunks better than
+    /// the ones underfoot. They place every vertex along its grid direction, which cannot follow
+    /// a stitched border, so they always hide cracks with [`EdgeMode::Skirts`].
+    Compact,
+}
+
+/// How chunk borders are kept crack-free where neighbouring chunks have different depths.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum EdgeMode {
+    /// Border vertices facing a coarser neighbour are moved onto that neighbour's edge.
+    #[default]
+    Stitch,
+    /// Every border gets a strip of triangles hanging below the surface that hides any gaps.
+    Skirts,
+}
+
+#[derive(Clone, Debug)]
+pub struct ChunkMeshBuilder {
     radius: Scalar,
+    subdivisions: usize,
     size: Vector2,
+    height: Heightfield,
+    edge_mode: EdgeMode,
+    split_factor: Scalar,
+    sea_level: Scalar,
+    biomes: Option<Biomes>,
 }
 
 #[allow(unused)]
-impl<const SUBDIVISIONS: usize> ChunkMeshBuilder<SUBDIVISIONS>
-where
-    [(); (SUBDIVISIONS + 2).pow(2)]:,
-    [(); (SUBDIVISIONS + 1).pow(2) * 6]:,
-{
-    const VERTEX_COUNT: usize = SUBDIVISIONS + 2;
-
-    pub fn new(radius: Scalar) -> Self {
+impl ChunkMeshBuilder {
+    pub fn new(radius: Scalar, height: Heightfield) -> Self {
         Self {
             radius,
+            subdivisions: CHUNK_SUBDIVISIONS,
             size: Vector2::splat(radius * 2.0),
+            height,
+            edge_mode: EdgeMode::default(),
+            split_factor: 1.5,
+            sea_level: 0.0,
+            biomes: None,
+        }
+    }
+
+    /// Sets how many vertices chunks get between their corners along each side.
+    pub fn with_subdivisions(mut self, subdivisions: usize) -> Self {
+        self.subdivisions = subdivisions;
+        self
+    }
+
+    pub fn with_edge_mode(mut self, edge_mode: EdgeMode) -> Self {
+        self.edge_mode = edge_mode;
+        self
+    }
+
+    /// Returns the number of vertices along each side of a chunk, not counting skirts.
+    pub fn vertex_count(&self) -> usize {
+        self.subdivisions + 2
+    }
+
+    /// Sets the [`LodSettings::split_factor`](super::LodSettings::split_factor) of the tree the
+    /// chunks belong to, which decides the distance at which vertices morph to the parent LOD.
+    pub fn with_split_factor(mut self, split_factor: Scalar) -> Self {
+        self.split_factor = split_factor;
+        self
+    }
+
+    /// Sets the [`Body::sea_level`](super::Body::sea_level) that altitudes are measured from.
+    pub fn with_sea_level(mut self, sea_level: Scalar) -> Self {
+        self.sea_level = sea_level;
+        self
+    }
+
+    /// Classifies vertices into `biomes`, storing their [`ATTRIBUTE_BIOME_WEIGHTS`].
+    pub fn with_biomes(mut self, biomes: Biomes) -> Self {
+        self.biomes = Some(biomes);
+        self
+    }
+
+    /// Returns a value identifying the meshes this builder produces, or `None` if its height
+    /// source has no [`HeightSource::fingerprint`](super::HeightSource::fingerprint).
+    pub fn fingerprint(&self) -> Option<u64> {
+        let mut hasher = FingerprintHasher::default();
+        hasher.write_u64(self.height.fingerprint()?);
+        self.hash_settings(&mut hasher);
+        Some(hasher.finish())
+    }
+
+    /// Returns a value identifying the settings of this builder, such as its resolution, split
+    /// factor and biomes, leaving out its height source.
+    pub fn settings_fingerprint(&self) -> u64 {
+        let mut hasher = FingerprintHasher::default();
+        self.hash_settings(&mut hasher);
+        hasher.finish()
+    }
+
+    fn hash_settings(&self, hasher: &mut FingerprintHasher) {
+        hasher.write_usize(self.subdivisions);
+        hasher.write_u8(self.edge_mode as u8);
+        hasher.write_u64(self.radius.to_bits() as u64);
+        hasher.write_u64(self.split_factor.to_bits() as u64);
+        hasher.write_u64(self.sea_level.to_bits() as u64);
+        if let Some(biomes) = &self.biomes {
+            hasher.write_u64(biomes.fingerprint());
         }
     }
 
     pub fn build(&self, bounds: &Rectangle, chunk_data: &ChunkData) -> Mesh {
-        let mut positions: [[f32; 3]; (SUBDIVISIONS + 2).pow(2)] =
-            [[0.0; 3]; (SUBDIVISIONS + 2).pow(2)];
-        let mut normals: [[f32; 3]; (SUBDIVISIONS + 2).pow(2)] =
-            [[0.0; 3]; (SUBDIVISIONS + 2).pow(2)];
-        let mut uvs: [[f32; 2]; (SUBDIVISIONS + 2).pow(2)] = [[0.0; 2]; (SUBDIVISIONS + 2).pow(2)];
-        let mut indices: [u32; (SUBDIVISIONS + 1).pow(2) * 6] = [0; (SUBDIVISIONS + 1).pow(2) * 6];
+        let vertex_count = self.vertex_count();
+        let mut positions: Vec<[f32; 3]> = vec![[0.0; 3]; vertex_count.pow(2)];
+        let mut normals: Vec<[f32; 3]> = vec![[0.0; 3]; vertex_count.pow(2)];
+        let mut tangents: Vec<[f32; 4]> = vec![[0.0; 4]; vertex_count.pow(2)];
+        let mut uvs: Vec<[f32; 2]> = vec![[0.0; 2]; vertex_count.pow(2)];
+        let mut morphs: Vec<[f32; 4]> = vec![[0.0; 4]; vertex_count.pow(2)];
+        let mut morph_normals: Vec<[f32; 3]> = vec![[0.0; 3]; vertex_count.pow(2)];
+        let mut altitude_slopes: Vec<[f32; 2]> = vec![[0.0; 2]; vertex_count.pow(2)];
+        let mut texture_positions: Vec<[f32; 3]> = vec![[0.0; 3]; vertex_count.pow(2)];
+        let mut biome_weights: Vec<[f32; MAX_BIOMES]> = Vec::new();
 
         let axis = chunk_data.hash.axis();
-        let (axis_normal, local_x, local_y) = AXIS_COORDINATE_FRAMES[&axis];
 
         let bounds_min = bounds.min / self.size;
         let bounds_max = bounds.max / self.size;
 
-        let step_x = (bounds_max.x - bounds_min.x) / (Self::VERTEX_COUNT - 1) as Scalar;
-        let step_y = (bounds_max.y - bounds_min.y) / (Self::VERTEX_COUNT - 1) as Scalar;
+        let step_x = (bounds_max.x - bounds_min.x) / (vertex_count - 1) as Scalar;
+        let step_y = (bounds_max.y - bounds_min.y) / (vertex_count - 1) as Scalar;
+
+        let texture_origin = chunk_data.center.rem_euclid(Vector::splat(TEXTURE_PERIOD));
+
+        // The parent merges back once observers are twice as far as this chunk splits at.
+        let morph_distance = (2.0 * bounds.size().x * self.split_factor) as f32;
+        let parent = ParentGrid::new(self, axis, (bounds_min, bounds_max));
+
+        // Sample the displaced surface on a grid with one extra ring of vertices around the chunk,
+        // so normals on the chunk border use the same neighbours as the adjacent chunk does.
+        let sample_count = vertex_count + 2;
+        let mut samples: Vec<(Vector, Vector)> = Vec::with_capacity(sample_count.pow(2));
+        for y in 0..sample_count {
+            for x in 0..sample_count {
+                let p_x = bounds_min.x + (x as Scalar - 1.0) * step_x;
+                let p_y = bounds_min.y + (y as Scalar - 1.0) * step_y;
+
+                samples.push(self.surface_point(axis, Vector2::new(p_x, p_y)));
+            }
+        }
 
-        let mut triangle_index = 0;
+        let mut surface: Vec<(Vector, Vector)> = Vec::with_capacity(vertex_count.pow(2));
 
-        for y in 0..Self::VERTEX_COUNT {
-            for x in 0..Self::VERTEX_COUNT {
+        for y in 0..vertex_count {
+            for x in 0..vertex_count {
                 let p_x = bounds_min.x + x as Scalar * step_x;
                 let p_y = bounds_min.y + y as Scalar * step_y;
 
-                let pos_on_cube = axis_normal + p_x * 2.0 * local_x + p_y * 2.0 * local_y;
-                let normal = unit_cube_to_sphere(pos_on_cube);
-                let pos = normal * self.radius;
+                let sample_index = (x + 1) + (y + 1) * sample_count;
+                let (direction, mut pos) = samples[sample_index];
+                if self.edge_mode == EdgeMode::Stitch {
+                    if let Some(stitched) = self.stitched_position(
+                        axis,
+                        (bounds_min, bounds_max),
+                        (x, y),
+                        Vector2::new(p_x, p_y),
+                        chunk_data.edge_lods,
+                    ) {
+                        pos = stitched;
+                    }
+                }
+                surface.push((direction, pos));
 
-                let index = x + (y * Self::VERTEX_COUNT);
+                let normal = surface_normal(
+                    direction,
+                    samples[sample_index - 1].1,
+                    samples[sample_index + 1].1,
+                    samples[sample_index - sample_count].1,
+                    samples[sample_index + sample_count].1,
+                );
+
+                let index = x + (y * vertex_count);
+                let uv = face_uv(Vector2::new(p_x, p_y) * 2.0);
+                let (morph_pos, morph_normal) = parent.as_ref().map_or((pos, normal), |parent| {
+                    parent.interpolate(Vector2::new(p_x, p_y))
+                });
+                let [morph_x, morph_y, morph_z] = to_array_f32(morph_pos - chunk_data.center);
+                morphs[index] = [morph_x, morph_y, morph_z, morph_distance];
+                morph_normals[index] = to_array_f32(morph_normal);
+                tangents[index] = surface_tangent(axis, normal);
+                let altitude = pos.length() - self.radius - self.sea_level;
+                let slope = normal.dot(direction).clamp(-1.0, 1.0).acos();
+                altitude_slopes[index] = [altitude as f32, slope as f32];
+                texture_positions[index] = to_array_f32(pos - chunk_data.center + texture_origin);
+                if let Some(biomes) = &self.biomes {
+                    let climate = biomes.climate.climate(direction, altitude, normal);
+                    biome_weights.push(biomes.weights(&climate));
+                }
 
                 #[cfg(feature = "f64")]
                 {
                     positions[index] = (pos - chunk_data.center).as_vec3().to_array();
                     normals[index] = normal.as_vec3().to_array();
-                    uvs[index] = spherical_uv(normal).as_vec2().to_array();
+                    uvs[index] = uv.as_vec2().to_array();
                 }
 
                 #[cfg(not(feature = "f64"))]
                 {
-                    positions[index] = (pos).to_array();
+                    positions[index] = (pos - chunk_data.center).to_array();
                     normals[index] = normal.to_array();
-                    uvs[index] = spherical_uv(pos).to_array();
+                    uvs[index] = uv.to_array();
                 }
+            }
+        }
 
-                if x < Self::VERTEX_COUNT - 1 && y < Self::VERTEX_COUNT - 1 {
-                    let i = triangle_index * 6;
-                    triangle_index += 1;
-                    match (p_x < 0.0, p_y < 0.0) {
-                        (false, false) => {
-                            // First triangle: CCW winding
-                            indices[i] = index as u32;
-                            indices[i + 1] = (index + Self::VERTEX_COUNT) as u32;
-                            indices[i + 2] = (index + Self::VERTEX_COUNT + 1) as u32;
-
-                            // Second triangle: CCW winding
-                            indices[i + 3] = index as u32;
-                            indices[i + 4] = (index + Self::VERTEX_COUNT + 1) as u32;
-                            indices[i + 5] = (index + 1) as u32;
-                        }
-                        (true, false) => {
-                            // First triangle: CCW winding
-                            indices[i] = index as u32;
-                            indices[i + 1] = (index + Self::VERTEX_COUNT) as u32;
-                            indices[i + 2] = (index + 1) as u32;
-
-                            // Second triangle: CCW winding
-                            indices[i + 3] = (index + 1) as u32;
-                            indices[i + 4] = (index + Self::VERTEX_COUNT) as u32;
-                            indices[i + 5] = (index + Self::VERTEX_COUNT + 1) as u32;
-                        }
-                        (false, true) => {
-                            // First triangle: CCW winding
-                            indices[i] = index as u32;
-                            indices[i + 1] = (index + Self::VERTEX_COUNT) as u32;
-                            indices[i + 2] = (index + 1) as u32;
-
-                            // Second triangle: CCW winding
-                            indices[i + 3] = (index + Self::VERTEX_COUNT) as u32;
-                            indices[i + 4] = (index + Self::VERTEX_COUNT + 1) as u32;
-                            indices[i + 5] = (index + 1) as u32;
-                        }
-                        (true, true) => {
-                            // First triangle: CCW winding
-                            indices[i] = index as u32;
-                            indices[i + 1] = (index + Self::VERTEX_COUNT) as u32;
-                            indices[i + 2] = (index + Self::VERTEX_COUNT + 1) as u32;
-
-                            // Second triangle: CCW winding
-                            indices[i + 3] = index as u32;
-                            indices[i + 4] = (index + Self::VERTEX_COUNT + 1) as u32;
-                            indices[i + 5] = (index + 1) as u32;
-                        }
+        if self.edge_mode == EdgeMode::Skirts {
+            // Hang each skirt one grid cell below the surface, which scales with the chunk's LOD.
+            let skirt_depth = step_x * self.size.x;
+            for edge in Edge::ALL {
+                for index in edge_vertices(vertex_count, edge) {
+                    let (direction, pos) = surface[index];
+                    positions.push(to_array_f32(
+                        pos - direction * skirt_depth - chunk_data.center,
+                    ));
+                    texture_positions.push(to_array_f32(
+                        pos - direction * skirt_depth - chunk_data.center + texture_origin,
+                    ));
+                    normals.push(normals[index]);
+                    tangents.push(tangents[index]);
+                    altitude_slopes.push(altitude_slopes[index]);
+                    if let Some(&weights) = biome_weights.get(index) {
+                        biome_weights.push(weights);
                     }
+                    uvs.push(uvs[index]);
+                    let [x, y, z] = to_array_f32(direction * skirt_depth);
+                    let [morph_x, morph_y, morph_z, morph_distance] = morphs[index];
+                    morphs.push([morph_x - x, morph_y - y, morph_z - z, morph_distance]);
+                    morph_normals.push(morph_normals[index]);
                 }
             }
         }
 
-        Mesh::new(
+        let mesh = Mesh::new(
             PrimitiveTopology::TriangleList,
             RenderAssetUsages::default(),
         )
-        .with_inserted_indices(Indices::U32(Vec::from(indices)))
-        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, Vec::from(positions))
-        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, Vec::from(normals))
-        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, Vec::from(uvs))
+        .with_inserted_indices(self.indices(bounds_min, Vector2::new(step_x, step_y)))
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_TANGENT, tangents)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+        .with_inserted_attribute(ATTRIBUTE_MORPH, morphs)
+        .with_inserted_attribute(ATTRIBUTE_MORPH_NORMAL, morph_normals)
+        .with_inserted_attribute(ATTRIBUTE_ALTITUDE_SLOPE, altitude_slopes)
+        .with_inserted_attribute(ATTRIBUTE_TEXTURE_POSITION, texture_positions);
+        if biome_weights.is_empty() {
+            mesh
+        } else {
+            mesh.with_inserted_attribute(ATTRIBUTE_BIOME_WEIGHTS, biome_weights)
+        }
+    }
+
+    /// Converts a mesh built by [`Self::build`] to [`VertexMode::Compact`], keeping its indices,
+    /// for a chunk whose record is at `slot`.
+    pub fn compact(&self, mesh: &Mesh, chunk_data: &ChunkData, slot: u32) -> Mesh {
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("expected chunk mesh to have positions");
+        };
+        let Some(VertexAttributeValues::Float32x3(normals)) =
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+        else {
+            panic!("expected chunk mesh to have normals");
+        };
+
+        let heights: Vec<f32> = positions
+            .iter()
+            .map(|position| {
+                let position =
+                    Vector::from_array(position.map(|v| v as Scalar)) + chunk_data.center;
+                (position.length() - self.radius) as f32
+            })
+            .collect();
+        let normals: Vec<[i16; 2]> = normals
+            .iter()
+            .map(|&normal| octahedral_encode(Vec3::from_array(normal)))
+            .collect();
+
+        let mut compact = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(ATTRIBUTE_HEIGHT, heights)
+        .with_inserted_attribute(
+            ATTRIBUTE_OCTAHEDRAL_NORMAL,
+            VertexAttributeValues::Snorm16x2(normals),
+        );
+        if let Some(VertexAttributeValues::Float32x4(weights)) =
+            mesh.attribute(ATTRIBUTE_BIOME_WEIGHTS)
+        {
+            let weights: Vec<[u8; 4]> = weights
+                .iter()
+                .map(|weights| weights.map(|weight| (weight.clamp(0.0, 1.0) * 255.0).round() as u8))
+                .collect();
+            compact.insert_attribute(
+                ATTRIBUTE_PACKED_BIOME_WEIGHTS,
+                VertexAttributeValues::Unorm8x4(weights),
+            );
+        }
+        if let Some(indices) = mesh.indices() {
+            compact.insert_indices(indices.clone());
+        }
+        set_chunk_slot(&mut compact, slot);
+        compact
+    }
+
+    /// Returns the unit-sphere direction and displaced position of a point given in face
+    /// coordinates normalized to `[-0.5, 0.5]`.
+    fn surface_point(&self, axis: Axis, face_pos: Vector2) -> (Vector, Vector) {
+        let pos_on_cube = face_to_cube(axis, face_pos * 2.0);
+        let direction = unit_cube_to_sphere(pos_on_cube).normalize();
+        (
+            direction,
+            direction * (self.radius + self.height.height(direction)),
+        )
+    }
+
+    /// Returns the index buffer of a chunk whose first vertex lies at `bounds_min`, with `step`
+    /// between vertices, in normalized face coordinates.
+    fn indices(&self, bounds_min: Vector2, step: Vector2) -> Indices {
+        // Quads on the negative side of the face are split along the other diagonal, see
+        // `IndexLayout::build`.
+        let negative_cells = |min: Scalar, step: Scalar| {
+            (0..self.vertex_count() - 1)
+                .take_while(|&cell| min + cell as Scalar * step < 0.0)
+                .count()
+        };
+        let layout = IndexLayout {
+            vertex_count: self.vertex_count(),
+            negative_cells: (
+                negative_cells(bounds_min.x, step.x),
+                negative_cells(bounds_min.y, step.y),
+            ),
+            skirts: self.edge_mode == EdgeMode::Skirts,
+        };
+        layout.indices()
+    }
+
+    /// Moves a border vertex onto the edge of a coarser neighbour, so the two chunks share the
+    /// same border line and no T-junction cracks open between them.
+    ///
+    /// Returns `None` when the vertex does not face a coarser neighbour or already coincides
+    /// with one of the neighbour's vertices.
+    fn stitched_position(
+        &self,
+        axis: Axis,
+        (bounds_min, bounds_max): (Vector2, Vector2),
+        (x, y): (usize, usize),
+        face_pos: Vector2,
+        edge_lods: EdgeLods,
+    ) -> Option<Vector> {
+        let last = self.vertex_count() - 1;
+        // Corners touch two edges, in which case the coarser neighbour wins.
+        let edge = [
+            (y == last).then_some(Edge::North),
+            (x == last).then_some(Edge::East),
+            (y == 0).then_some(Edge::South),
+            (x == 0).then_some(Edge::West),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|edge| edge_lods[*edge] > 0)
+        .max_by_key(|edge| edge_lods[*edge])?;
+
+        let along_x = matches!(edge, Edge::North | Edge::South);
+        let (coord, chunk_min, chunk_max) = if along_x {
+            (face_pos.x, bounds_min.x, bounds_max.x)
+        } else {
+            (face_pos.y, bounds_min.y, bounds_max.y)
+        };
+
+        // The neighbour is an aligned quadtree cell `2^lod` times our size, with the same number
+        // of segments along its edge.
+        let coarse_size = (chunk_max - chunk_min) * (1u64 << edge_lods[edge]) as Scalar;
+        let coarse_min =
+            (((chunk_min + chunk_max) * 0.5 + 0.5) / coarse_size).floor() * coarse_size - 0.5;
+        let coarse_step = coarse_size / last as Scalar;
+
+        let t = (coord - coarse_min) / coarse_step;
+        let segment = t.floor().clamp(0.0, (last - 1) as Scalar);
+        let fraction = t - segment;
+        if fraction < 1e-6 || fraction > 1.0 - 1e-6 {
+            return None;
+        }
+
+        let start = coarse_min + segment * coarse_step;
+        let on_edge = |coord: Scalar| {
+            if along_x {
+                Vector2::new(coord, face_pos.y)
+            } else {
+                Vector2::new(face_pos.x, coord)
+            }
+        };
+        let (_, start_pos) = self.surface_point(axis, on_edge(start));
+        let (_, end_pos) = self.surface_point(axis, on_edge(start + coarse_step));
+        Some(start_pos.lerp(end_pos, fraction))
+    }
+}
+
+/// Points the vertices of a compact chunk mesh at the record in `slot`, unless they already are.
+pub fn set_chunk_slot(mesh: &mut Mesh, slot: u32) {
+    if let Some(VertexAttributeValues::Uint32(slots)) = mesh.attribute(ATTRIBUTE_CHUNK_SLOT) {
+        if slots.first() == Some(&slot) {
+            return;
+        }
+    }
+    let slots = vec![slot; mesh.count_vertices()];
+    mesh.insert_attribute(ATTRIBUTE_CHUNK_SLOT, slots);
+}
+
+#[inline]
+fn to_array_f32(vector: Vector) -> [f32; 3] {
+    #[cfg(feature = "f64")]
+    {
+        vector.as_vec3().to_array()
+    }
+
+    #[cfg(not(feature = "f64"))]
+    {
+        vector.to_array()
+    }
+}
+
+/// Encodes a unit vector as a point on an octahedron unfolded onto the `[-1, 1]` square, in
+/// normalized 16-bit components.
+fn octahedral_encode(normal: Vec3) -> [i16; 2] {
+    let octahedron = normal / normal.abs().element_sum();
+    let folded = if octahedron.z < 0.0 {
+        (Vec2::ONE - octahedron.yx().abs()) * octahedron.xy().signum()
+    } else {
+        octahedron.xy()
+    };
+    folded
+        .to_array()
+        .map(|component| (component.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16)
+}
+
+/// Computes the surface normal at a grid vertex from the central differences of its four
+/// neighbours, oriented to point away from the body's center.
+fn surface_normal(
+    direction: Vector,
+    left: Vector,
+    right: Vector,
+    down: Vector,
+    up: Vector,
+) -> Vector {
+    let normal = (right - left).cross(up - down).normalize_or_zero();
+    if normal == Vector::ZERO {
+        return direction;
+    }
+    if normal.dot(direction) < 0.0 {
+        -normal
+    } else {
+        normal
+    }
+}
+
+/// Computes the tangent of a vertex on the face of `axis` from its normal.
+///
+/// The tangent is the face's local x axis projected onto the surface, and its handedness makes
+/// the bitangent follow the face's local y axis. Vertices with the same normal get the same
+/// tangent, so normal maps laid out in face coordinates continue across chunk borders, and the
+/// frame turns with the face coordinates where chunks meet across a cube edge.
+fn surface_tangent(axis: Axis, normal: Vector) -> [f32; 4] {
+    let (_, local_x, local_y) = AXIS_COORDINATE_FRAMES[&axis];
+    let tangent = (local_x - normal * normal.dot(local_x))
+        .try_normalize()
+        .unwrap_or_else(|| normal.cross(local_y).normalize());
+    let handedness = normal.cross(tangent).dot(local_y).signum();
+    let [x, y, z] = to_array_f32(tangent);
+    [x, y, z, handedness as f32]
+}
+
+/// The vertices of a chunk's parent LOD, sampled once to interpolate the geomorph targets of the
+/// chunk's vertices from.
+struct ParentGrid {
+    /// The parent's minimum corner, in face coordinates normalized to `[-0.5, 0.5]`.
+    min: Vector2,
+    step: Vector2,
+    vertex_count: usize,
+    /// The parent's surface on its grid, with one extra ring of vertices for normals.
+    samples: Vec<(Vector, Vector)>,
+}
+
+impl ParentGrid {
+    /// Samples the parent of the chunk within `bounds`, or returns `None` for chunks that cover
+    /// a whole face and have no parent.
+    fn new(
+        builder: &ChunkMeshBuilder,
+        axis: Axis,
+        (bounds_min, bounds_max): (Vector2, Vector2),
+    ) -> Option<Self> {
+        let size = bounds_max - bounds_min;
+        if size.x >= 1.0 - 1e-9 {
+            return None;
+        }
+
+        // The parent is the aligned quadtree cell twice our size, with the same number of
+        // segments as this chunk.
+        let vertex_count = builder.vertex_count();
+        let parent_size = size * 2.0;
+        let min =
+            (((bounds_min + bounds_max) * 0.5 + 0.5) / parent_size).floor() * parent_size - 0.5;
+        let step = parent_size / (vertex_count - 1) as Scalar;
+
+        let sample_count = vertex_count + 2;
+        let mut samples = Vec::with_capacity(sample_count.pow(2));
+        for y in 0..sample_count {
+            for x in 0..sample_count {
+                let offset = Vector2::new(x as Scalar - 1.0, y as Scalar - 1.0);
+                samples.push(builder.surface_point(axis, min + offset * step));
+            }
+        }
+        Some(Self {
+            min,
+            step,
+            vertex_count,
+            samples,
+        })
+    }
+
+    /// Returns the position and normal of the parent's vertex at `(x, y)`, as its mesh has them.
+    fn vertex(&self, x: usize, y: usize) -> (Vector, Vector) {
+        let sample_count = self.vertex_count + 2;
+        let index = (x + 1) + (y + 1) * sample_count;
+        let (direction, position) = self.samples[index];
+        let normal = surface_normal(
+            direction,
+            self.samples[index - 1].1,
+            self.samples[index + 1].1,
+            self.samples[index - sample_count].1,
+            self.samples[index + sample_count].1,
+        );
+        (position, normal)
+    }
+
+    /// Returns where the parent's mesh places the point at `face_pos`, and its normal there, by
+    /// interpolating across the parent triangle that contains it.
+    fn interpolate(&self, face_pos: Vector2) -> (Vector, Vector) {
+        let last = self.vertex_count - 1;
+        let t = (face_pos - self.min) / self.step;
+        let cell = t
+            .floor()
+            .clamp(Vector2::ZERO, Vector2::splat((last - 1) as Scalar));
+        let f = t - cell;
+        let corner = |dx: usize, dy: usize| self.vertex(cell.x as usize + dx, cell.y as usize + dy);
+
+        // Mirror the diagonal that `build` picks for the cell's quadrant of the face.
+        let cell_min = self.min + cell * self.step;
+        let weighted = if (cell_min.x < 0.0) == (cell_min.y < 0.0) {
+            if f.x >= f.y {
+                [
+                    (corner(0, 0), 1.0 - f.x),
+                    (corner(1, 0), f.x - f.y),
+                    (corner(1, 1), f.y),
+                ]
+            } else {
+                [
+                    (corner(0, 0), 1.0 - f.y),
+                    (corner(0, 1), f.y - f.x),
+                    (corner(1, 1), f.x),
+                ]
+            }
+        } else if f.x + f.y <= 1.0 {
+            [
+                (corner(0, 0), 1.0 - f.x - f.y),
+                (corner(1, 0), f.x),
+                (corner(0, 1), f.y),
+            ]
+        } else {
+            [
+                (corner(1, 1), f.x + f.y - 1.0),
+                (corner(1, 0), 1.0 - f.y),
+                (corner(0, 1), 1.0 - f.x),
+            ]
+        };
+        let (position, normal) = weighted.into_iter().fold(
+            (Vector::ZERO, Vector::ZERO),
+            |(position, normal), ((corner_position, corner_normal), weight)| {
+                (
+                    position + corner_position * weight,
+                    normal + corner_normal * weight,
+                )
+            },
+        );
+        (position, normal.normalize())
+    }
+}
+
+lazy_static! {
+    /// Index buffers of every [`IndexLayout`] built so far, so each layout is only triangulated
+    /// once.
+    static ref INDEX_BUFFERS: Mutex<HashMap<IndexLayout, Indices>> = Mutex::new(HashMap::default());
+}
+
+/// Everything the index buffer of a chunk mesh depends on.
+///
+/// Buffers are precomputed once per layout, but Bevy meshes own their indices, so every chunk
+/// [`Mesh`] still holds and uploads a copy. `u16` indices halve the size of that copy.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct IndexLayout {
+    /// Vertices along each side of the chunk grid.
+    vertex_count: usize,
+    /// Columns and rows of quads, counted from the chunk's minimum corner, that lie on the
+    /// negative side of the face.
+    negative_cells: (usize, usize),
+    /// Whether skirt vertices follow the grid, see [`EdgeMode::Skirts`].
+    skirts: bool,
+}
+
+impl IndexLayout {
+    /// Returns a copy of the layout's precomputed index buffer, building it on first use.
+    fn indices(self) -> Indices {
+        INDEX_BUFFERS
+            .lock()
+            .expect("expected index buffer lock not to be poisoned")
+            .entry(self)
+            .or_insert_with(|| self.build())
+            .clone()
+    }
+
+    /// Builds the index buffer, with `u16` indices whenever the vertices fit.
+    fn build(self) -> Indices {
+        let n = self.vertex_count;
+        let mut indices: Vec<u32> = Vec::with_capacity((n - 1).pow(2) * 6 + 4 * (n - 1) * 6);
+        for y in 0..n - 1 {
+            for x in 0..n - 1 {
+                let index = (x + y * n) as u32;
+                let (up, up_right, right) = (index + n as u32, index + n as u32 + 1, index + 1);
+                // Quads are split along the diagonal pointing away from the face's center, which
+                // keeps the triangulation symmetric across the face.
+                match (x < self.negative_cells.0, y < self.negative_cells.1) {
+                    (false, false) | (true, true) => {
+                        indices.extend_from_slice(&[index, up, up_right, index, up_right, right]);
+                    }
+                    (true, false) => {
+                        indices.extend_from_slice(&[index, up, right, right, up, up_right]);
+                    }
+                    (false, true) => {
+                        indices.extend_from_slice(&[index, up, right, up, up_right, right]);
+                    }
+                }
+            }
+        }
+
+        let mut vertex_total = n * n;
+        if self.skirts {
+            for edge in Edge::ALL {
+                let base = vertex_total as u32;
+                for (i, pair) in edge_vertices(n, edge).windows(2).enumerate() {
+                    let (a, b) = (pair[0] as u32, pair[1] as u32);
+                    let (skirt_a, skirt_b) = (base + i as u32, base + i as u32 + 1);
+                    // Skirts face away from the chunk, where gaps to its neighbours are seen from.
+                    match edge {
+                        Edge::North | Edge::West => {
+                            indices.extend_from_slice(&[a, skirt_a, b, b, skirt_a, skirt_b]);
+                        }
+                        Edge::South | Edge::East => {
+                            indices.extend_from_slice(&[a, b, skirt_a, b, skirt_b, skirt_a]);
+                        }
+                    }
+                }
+                vertex_total += n;
+            }
+        }
+
+        if vertex_total <= u16::MAX as usize + 1 {
+            Indices::U16(indices.into_iter().map(|index| index as u16).collect())
+        } else {
+            Indices::U32(indices)
+        }
+    }
+}
+
+/// Returns the indices of the vertices along an edge of a grid with `vertex_count` vertices per
+/// side, in increasing order along the edge.
+fn edge_vertices(vertex_count: usize, edge: Edge) -> Vec<usize> {
+    let last = vertex_count - 1;
+    (0..vertex_count)
+        .map(|i| match edge {
+            Edge::North => i + last * vertex_count,
+            Edge::East => last + i * vertex_count,
+            Edge::South => i,
+            Edge::West => i * vertex_count,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::quad_tree::Quadrant;
+    use crate::plugins::terrain::cube_tree::{ChunkHash, CubeTree};
+    use crate::plugins::terrain::height::FractalNoise;
+    use avian3d::math::AsF32;
+    use bevy::render::mesh::VertexAttributeValues;
+
+    const RADIUS: Scalar = 1000.0;
+
+    /// Returns hilly terrain and a builder meshing it at the resolution most tests use.
+    fn hilly() -> (Heightfield, ChunkMeshBuilder) {
+        let height = Heightfield::from(FractalNoise {
+            amplitude: 50.0,
+            ..FractalNoise::EARTH
+        });
+        let builder = ChunkMeshBuilder::new(RADIUS, height.clone()).with_subdivisions(4);
+        (height, builder)
+    }
+
+    fn chunk_data(height: &Heightfield, axis: Axis, bounds: &Rectangle) -> ChunkData {
+        ChunkData::new(axis, bounds, RADIUS, &**height, ChunkHash::new_root(axis))
+    }
+
+    fn vertices(mesh: &Mesh, center: Vector) -> Vec<(Vector, Vector)> {
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("expected positions");
+        };
+        let Some(VertexAttributeValues::Float32x3(normals)) =
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+        else {
+            panic!("expected normals");
+        };
+        positions
+            .iter()
+            .zip(normals.iter())
+            .map(|(position, normal)| {
+                (
+                    Vector::from_array(position.map(|v| v as Scalar)) + center,
+                    Vector::from_array(normal.map(|v| v as Scalar)),
+                )
+            })
+            .collect()
+    }
+
+    /// A chunk and the parent it morphs toward, which covers four times its area.
+    struct MorphFixture {
+        vertex_count: usize,
+        parent_vertices: Vec<(Vector, Vector)>,
+        child: ChunkData,
+        child_mesh: Mesh,
+    }
+
+    impl MorphFixture {
+        fn new() -> Self {
+            let (height, builder) = hilly();
+            let axis = Axis::X;
+            let parent_bounds = Rectangle::from_corners(Vector2::ZERO, Vector2::splat(RADIUS));
+            let parent = chunk_data(&height, axis, &parent_bounds);
+            let child_bounds = Rectangle::from_corners(Vector2::ZERO, Vector2::splat(RADIUS / 2.0));
+            let child = chunk_data(&height, axis, &child_bounds);
+            Self {
+                vertex_count: builder.vertex_count(),
+                parent_vertices: vertices(&builder.build(&parent_bounds, &parent), parent.center),
+                child_mesh: builder.build(&child_bounds, &child),
+                child,
+            }
+        }
+
+        fn parent_vertex(&self, x: usize, y: usize) -> (Vector, Vector) {
+            self.parent_vertices[x + y * self.vertex_count]
+        }
+    }
+
+    #[test]
+    fn test_normals_match_across_chunk_and_face_borders() {
+        let (height, builder) = hilly();
+        let tree = CubeTree::new(RADIUS, height);
+
+        let chunks: Vec<Vec<(Vector, Vector)>> = tree
+            .iter()
+            .map(|(bounds, data)| vertices(&builder.build(bounds, data), data.center))
+            .collect();
+
+        let mut shared = 0;
+        for (i, a) in chunks.iter().enumerate() {
+            for b in chunks.iter().skip(i + 1) {
+                for (pos_a, normal_a) in a {
+                    for (pos_b, normal_b) in b {
+                        if pos_a.distance(*pos_b) < 1e-2 {
+                            shared += 1;
+                            assert!(
+                                normal_a.distance(*normal_b) < 1e-3,
+                                "normals differ at {pos_a:?}: {normal_a:?} vs {normal_b:?}"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        assert!(shared > 0, "expected chunks to share border vertices");
+    }
+
+    #[test]
+    fn test_stitched_edge_lies_on_coarse_neighbor() {
+        let (height, builder) = hilly();
+        let axis = Axis::X;
+
+        let coarse_bounds = Rectangle::from_corners(Vector2::ZERO, Vector2::splat(RADIUS));
+        let coarse = chunk_data(&height, axis, &coarse_bounds);
+        let fine_bounds = Rectangle::from_corners(
+            Vector2::new(0.0, -RADIUS / 2.0),
+            Vector2::new(RADIUS / 2.0, 0.0),
+        );
+        let mut fine = chunk_data(&height, axis, &fine_bounds);
+        fine.edge_lods[Edge::North] = 1;
+
+        let vertex_count = builder.vertex_count();
+        let coarse_edge: Vec<Vector> =
+            vertices(&builder.build(&coarse_bounds, &coarse), coarse.center)
+                .into_iter()
+                .take(vertex_count)
+                .map(|(position, _)| position)
+                .collect();
+        let fine_edge: Vec<Vector> = vertices(&builder.build(&fine_bounds, &fine), fine.center)
+            .into_iter()
+            .skip(vertex_count * (vertex_count - 1))
+            .map(|(position, _)| position)
+            .collect();
+
+        for point in fine_edge {
+            let distance = coarse_edge
+                .windows(2)
+                .map(|segment| {
+                    let (a, b) = (segment[0], segment[1]);
+                    let t = ((point - a).dot(b - a) / (b - a).length_squared()).clamp(0.0, 1.0);
+                    point.distance(a + (b - a) * t)
+                })
+                .fold(Scalar::MAX, Scalar::min);
+            assert!(
+                distance < 1e-2,
+                "{point:?} is {distance} off the coarse edge"
+            );
+        }
+    }
+
+    #[test]
+    fn test_morph_targets_match_parent_vertices() {
+        let fixture = MorphFixture::new();
+        let child_vertices = vertices(&fixture.child_mesh, fixture.child.center);
+        let Some(VertexAttributeValues::Float32x4(morphs)) =
+            fixture.child_mesh.attribute(ATTRIBUTE_MORPH)
+        else {
+            panic!("expected morph targets");
+        };
+        let morph_target = |x: usize, y: usize| {
+            let [mx, my, mz, _] = morphs[x + y * fixture.vertex_count];
+            Vector::new(mx as Scalar, my as Scalar, mz as Scalar) + fixture.child.center
+        };
+        let parent_vertex = |x: usize, y: usize| fixture.parent_vertex(x, y).0;
+
+        // Every other child vertex coincides with a parent vertex, and the ones between them
+        // morph onto the parent's edges.
+        for y in 0..3 {
+            for x in 0..3 {
+                assert!(morph_target(2 * x, 2 * y).distance(parent_vertex(x, y)) < 1e-2);
+                // Positions and morph targets share the chunk center as their origin.
+                let position = child_vertices[2 * x + 2 * y * fixture.vertex_count].0;
+                assert!(morph_target(2 * x, 2 * y).distance(position) < 1e-2);
+            }
+        }
+        let midpoint = (parent_vertex(0, 0) + parent_vertex(1, 0)) / 2.0;
+        assert!(morph_target(1, 0).distance(midpoint) < 1e-2);
+    }
+
+    #[test]
+    fn test_morph_normals_match_parent_normals() {
+        let fixture = MorphFixture::new();
+        let Some(VertexAttributeValues::Float32x3(morph_normals)) =
+            fixture.child_mesh.attribute(ATTRIBUTE_MORPH_NORMAL)
+        else {
+            panic!("expected morph normals");
+        };
+        let morph_normal = |x: usize, y: usize| {
+            Vector::from_array(morph_normals[x + y * fixture.vertex_count].map(|v| v as Scalar))
+        };
+        let parent_normal = |x: usize, y: usize| fixture.parent_vertex(x, y).1;
+
+        // Child vertices on parent vertices take their normals, and the ones between them blend
+        // the normals of the parent's edge.
+        for y in 0..3 {
+            for x in 0..3 {
+                assert!(morph_normal(2 * x, 2 * y).distance(parent_normal(x, y)) < 1e-4);
+            }
+        }
+        let blended = (parent_normal(0, 0) + parent_normal(1, 0)).normalize();
+        assert!(morph_normal(1, 0).distance(blended) < 1e-4);
+    }
+
+    #[test]
+    fn test_normals_point_outwards() {
+        let (height, builder) = hilly();
+        for axis in Axis::ALL {
+            let bounds = Rectangle::from_corners(Vector2::splat(-RADIUS), Vector2::splat(RADIUS));
+            let data = chunk_data(&height, axis, &bounds);
+            for (position, normal) in vertices(&builder.build(&bounds, &data), data.center) {
+                assert!(normal.dot(position.normalize()) > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_index_buffers_are_precomputed_u16() {
+        let height = Heightfield::from(FractalNoise::FLAT);
+        let axis = Axis::X;
+        let chunk = |min: Scalar| {
+            let bounds =
+                Rectangle::from_corners(Vector2::splat(min), Vector2::splat(min + RADIUS / 2.0));
+            let data = chunk_data(&height, axis, &bounds);
+            (bounds, data)
+        };
+        let (bounds, data) = chunk(0.0);
+        let (other_bounds, other) = chunk(RADIUS / 2.0);
+
+        for edge_mode in [EdgeMode::Stitch, EdgeMode::Skirts] {
+            let builder = ChunkMeshBuilder::new(RADIUS, height.clone())
+                .with_subdivisions(8)
+                .with_edge_mode(edge_mode);
+            let mesh = builder.build(&bounds, &data);
+            let Some(Indices::U16(indices)) = mesh.indices() else {
+                panic!("expected u16 indices");
+            };
+            assert!(indices
+                .iter()
+                .all(|&index| (index as usize) < mesh.count_vertices()));
+
+            let other_mesh = builder.build(&other_bounds, &other);
+            let other_indices = other_mesh.indices().unwrap().iter();
+            assert!(indices
+                .iter()
+                .map(|&index| index as usize)
+                .eq(other_indices));
+        }
+    }
+
+    #[test]
+    fn test_skirts_face_away_from_the_chunk() {
+        let (height, builder) = hilly();
+        let builder = builder.with_edge_mode(EdgeMode::Skirts);
+        let axis = Axis::Z;
+        let bounds = Rectangle::from_corners(Vector2::ZERO, Vector2::splat(RADIUS / 2.0));
+        let data = chunk_data(&height, axis, &bounds);
+        let mesh = builder.build(&bounds, &data);
+        let positions: Vec<Vector> = vertices(&mesh, Vector::ZERO)
+            .into_iter()
+            .map(|(position, _)| position)
+            .collect();
+        let indices: Vec<usize> = mesh.indices().expect("expected indices").iter().collect();
+        let triangles: Vec<[Vector; 3]> = indices
+            .chunks(3)
+            .map(|triangle| [0, 1, 2].map(|corner| positions[triangle[corner]]))
+            .collect();
+
+        let vertex_count = builder.vertex_count();
+        let grid_triangles = (vertex_count - 1).pow(2) * 2;
+        assert_eq!(triangles.len(), grid_triangles + 4 * (vertex_count - 1) * 2);
+        // Triangles facing `outward` all share the winding of the first grid triangle.
+        let winding = |[a, b, c]: [Vector; 3], outward: Vector| (b - a).cross(c - a).dot(outward);
+        let facing = |[a, b, c]: [Vector; 3]| winding([a, b, c], a + data.center).signum();
+        let front = facing(triangles[0]);
+        for &triangle in &triangles[..grid_triangles] {
+            assert_eq!(facing(triangle), front);
+        }
+        // Positions are relative to the chunk's center, so skirts lie outward of it.
+        for &triangle in &triangles[grid_triangles..] {
+            let centroid = (triangle[0] + triangle[1] + triangle[2]) / 3.0;
+            assert_eq!(winding(triangle, centroid).signum(), front);
+        }
+    }
+
+    #[test]
+    fn test_compact_vertices_rebuild_the_surface() {
+        let (height, builder) = hilly();
+        let builder = builder.with_edge_mode(EdgeMode::Skirts);
+        let axis = Axis::Y;
+        let bounds = Rectangle::from_corners(
+            Vector2::new(-RADIUS / 2.0, 0.0),
+            Vector2::new(0.0, RADIUS / 2.0),
+        );
+        let data = chunk_data(&height, axis, &bounds);
+        let mesh = builder.build(&bounds, &data);
+        let compact = builder.compact(&mesh, &data, 3);
+        assert_eq!(compact.count_vertices(), mesh.count_vertices());
+        let Some(VertexAttributeValues::Uint32(slots)) = compact.attribute(ATTRIBUTE_CHUNK_SLOT)
+        else {
+            panic!("expected chunk slots");
+        };
+        assert!(slots.iter().all(|&slot| slot == 3));
+
+        let Some(VertexAttributeValues::Float32(heights)) = compact.attribute(ATTRIBUTE_HEIGHT)
+        else {
+            panic!("expected heights");
+        };
+        let Some(VertexAttributeValues::Snorm16x2(encoded)) =
+            compact.attribute(ATTRIBUTE_OCTAHEDRAL_NORMAL)
+        else {
+            panic!("expected octahedral normals");
+        };
+        let vertex_count = builder.vertex_count();
+        let step = bounds.size() / (vertex_count - 1) as Scalar;
+        for (index, (position, normal)) in vertices(&mesh, data.center).into_iter().enumerate() {
+            let decoded = octahedral_decode(encoded[index]);
+            assert!(
+                decoded.distance(normal.f32()) < 1e-3,
+                "{decoded} != {normal}"
+            );
+            if index < vertex_count * vertex_count {
+                // The compact vertex shader places grid vertices along their grid direction.
+                let grid = Vector2::new(
+                    (index % vertex_count) as Scalar,
+                    (index / vertex_count) as Scalar,
+                );
+                let face = (bounds.min + grid * step) / (RADIUS * 2.0);
+                let (direction, _) = builder.surface_point(axis, face);
+                let rebuilt = direction * (RADIUS + heights[index] as Scalar);
+                assert!(rebuilt.distance(position) < 1e-3, "{rebuilt} != {position}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_tangents_follow_the_face_frame() {
+        let (height, builder) = hilly();
+        let builder = builder.with_edge_mode(EdgeMode::Skirts);
+        for axis in Axis::ALL {
+            let (_, local_x, local_y) = AXIS_COORDINATE_FRAMES[&axis];
+            let bounds = Rectangle::from_corners(Vector2::splat(-RADIUS), Vector2::splat(RADIUS));
+            let data = chunk_data(&height, axis, &bounds);
+            let mesh = builder.build(&bounds, &data);
+            let Some(VertexAttributeValues::Float32x4(tangents)) =
+                mesh.attribute(Mesh::ATTRIBUTE_TANGENT)
+            else {
+                panic!("expected tangents");
+            };
+            assert_eq!(tangents.len(), mesh.count_vertices());
+            let vertices = vertices(&mesh, data.center);
+            for ((_, normal), &[x, y, z, w]) in vertices.into_iter().zip(tangents) {
+                let tangent = Vector::new(x as Scalar, y as Scalar, z as Scalar);
+                assert!((tangent.length() - 1.0).abs() < 1e-4);
+                assert!(tangent.dot(normal).abs() < 1e-4);
+                assert!(tangent.dot(local_x) > 0.0);
+                assert!((normal.cross(tangent) * w as Scalar).dot(local_y) > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_uvs_follow_face_coordinates() {
+        let height = Heightfield::from(FractalNoise::MOON);
+        let builder = ChunkMeshBuilder::new(RADIUS, height.clone()).with_subdivisions(4);
+        let axis = Axis::NegZ;
+        let bounds = Rectangle::from_corners(Vector2::new(-RADIUS, 0.0), Vector2::new(0.0, RADIUS));
+        let data = chunk_data(&height, axis, &bounds);
+        let mesh = builder.build(&bounds, &data);
+        let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute(Mesh::ATTRIBUTE_UV_0)
+        else {
+            panic!("expected uvs");
+        };
+        let vertex_count = builder.vertex_count();
+        assert_eq!(uvs[0], [0.0, 0.5]);
+        assert_eq!(uvs[vertex_count - 1], [0.5, 0.5]);
+        assert_eq!(uvs[vertex_count * vertex_count - 1], [0.5, 1.0]);
+    }
+
+    #[test]
+    fn test_texture_positions_continue_across_chunks() {
+        let height = Heightfield::from(FractalNoise::MOON);
+        let builder = ChunkMeshBuilder::new(RADIUS, height.clone()).with_subdivisions(4);
+        let axis = Axis::X;
+        let left = Rectangle::from_corners(Vector2::new(-RADIUS, 0.0), Vector2::new(0.0, RADIUS));
+        let right = Rectangle::from_corners(Vector2::ZERO, Vector2::splat(RADIUS));
+        let to_vector = |position: &[f32; 3]| Vector::from_array(position.map(|v| v as Scalar));
+        let texture_positions = |bounds: &Rectangle| {
+            let data = chunk_data(&height, axis, bounds);
+            let mesh = builder.build(bounds, &data);
+            let Some(VertexAttributeValues::Float32x3(texture_positions)) =
+                mesh.attribute(ATTRIBUTE_TEXTURE_POSITION).cloned()
+            else {
+                panic!("expected texture positions");
+            };
+            let vertices = vertices(&mesh, data.center);
+            for ((position, _), texture_position) in vertices.into_iter().zip(&texture_positions) {
+                // Texture positions are body positions shifted by whole periods.
+                let offset = (position - to_vector(texture_position)) / TEXTURE_PERIOD;
+                assert!((offset - offset.round()).abs().max_element() < 1e-3);
+            }
+            texture_positions
+        };
+
+        // The shared edge has the same texture position in both chunks, up to whole periods.
+        let vertex_count = builder.vertex_count();
+        let (left, right) = (texture_positions(&left), texture_positions(&right));
+        for y in 0..vertex_count {
+            let a = to_vector(&left[y * vertex_count + vertex_count - 1]);
+            let b = to_vector(&right[y * vertex_count]);
+            let offset = (a - b) / TEXTURE_PERIOD;
+            assert!((offset - offset.round()).abs().max_element() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_altitude_slope_matches_the_surface() {
+        let (height, builder) = hilly();
+        let axis = Axis::Y;
+        let bounds = Rectangle::from_corners(Vector2::splat(-RADIUS), Vector2::ZERO);
+        let data = chunk_data(&height, axis, &bounds);
+        let mesh = builder.build(&bounds, &data);
+        let Some(VertexAttributeValues::Float32x2(altitude_slopes)) =
+            mesh.attribute(ATTRIBUTE_ALTITUDE_SLOPE)
+        else {
+            panic!("expected altitudes and slopes");
+        };
+        let grid_vertices = builder.vertex_count().pow(2);
+        let vertices = vertices(&mesh, data.center);
+        for ((position, normal), &[altitude, slope]) in vertices
+            .into_iter()
+            .zip(altitude_slopes)
+            .take(grid_vertices)
+        {
+            assert!((position.length() - RADIUS - altitude as Scalar).abs() < 1e-2);
+            let expected = normal.dot(position.normalize()).clamp(-1.0, 1.0).acos();
+            assert!((expected - slope as Scalar).abs() < 1e-3);
+        }
+    }
+
+    fn octahedral_decode(encoded: [i16; 2]) -> Vec3 {
+        let [x, y] = encoded.map(|component| component as f32 / i16::MAX as f32);
+        let mut normal = Vec3::new(x, y, 1.0 - x.abs() - y.abs());
+        let fold = (-normal.z).max(0.0);
+        normal.x -= fold.copysign(normal.x);
+        normal.y -= fold.copysign(normal.y);
+        normal.normalize()
     }
 }
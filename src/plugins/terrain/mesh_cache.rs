@@ -0,0 +1,271 @@
+use avian3d::prelude::Collider;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::collections::BTreeMap;
+
+use super::cube_tree::{ChunkHash, EdgeLods};
+
+/// Identifies a generated chunk mesh: the body it belongs to, the chunk, the edge LODs it was
+/// stitched for, and the settings it was built with.
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ChunkMeshKey {
+    pub body: Entity,
+    pub hash: ChunkHash,
+    pub edge_lods: EdgeLods,
+    /// The [`settings_fingerprint`](super::mesh::ChunkMeshBuilder::settings_fingerprint) of the
+    /// chunk's builder, so chunks built before the body's [`LodSettings`](super::LodSettings) or
+    /// [`Biomes`](super::Biomes) changed are not respawned.
+    pub settings: u64,
+}
+
+/// The mesh, collider and ocean mesh of a chunk that is no longer spawned.
+#[derive(Clone, Debug)]
+pub struct CachedChunk {
+    pub mesh: Handle<Mesh>,
+    pub collider: Option<Collider>,
+    pub ocean: Option<Handle<Mesh>>,
+    /// Approximate memory held by the meshes and collider.
+    pub bytes: usize,
+}
+
+impl CachedChunk {
+    pub fn new(mesh_handle: Handle<Mesh>, mesh: &Mesh, collider: Option<Collider>) -> Self {
+        let collider_bytes = collider
+            .as_ref()
+            .and_then(|collider| collider.shape().as_trimesh())
+            .map_or(0, |trimesh| {
+                std::mem::size_of_val(trimesh.vertices()) + std::mem::size_of_val(trimesh.indices())
+            });
+        Self {
+            mesh: mesh_handle,
+            collider,
+            ocean: None,
+            bytes: mesh_bytes(mesh) + collider_bytes,
+        }
+    }
+
+    pub fn with_ocean(mut self, ocean_handle: Handle<Mesh>, ocean: &Mesh) -> Self {
+        self.ocean = Some(ocean_handle);
+        self.bytes += mesh_bytes(ocean);
+        self
+    }
+}
+
+fn mesh_bytes(mesh: &Mesh) -> usize {
+    mesh.get_vertex_buffer_size() + mesh.get_index_buffer_bytes().map_or(0, <[u8]>::len)
+}
+
+/// Hit and miss counts of a [`ChunkMeshCache`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Reflect)]
+pub struct ChunkMeshCacheStats {
+    /// Chunks spawned from the cache instead of being generated.
+    pub hits: u64,
+    /// Chunks that had to be generated.
+    pub misses: u64,
+    /// Entries dropped to stay within the budget.
+    pub evictions: u64,
+}
+
+/// Keeps the meshes and colliders of recently despawned chunks, so chunks that come back into
+/// view are respawned without generating them again.
+///
+/// Least recently cached entries are dropped once the cache holds more than `budget` bytes.
+#[derive(Resource, Debug)]
+pub struct ChunkMeshCache {
+    budget: usize,
+    bytes: usize,
+    entries: HashMap<ChunkMeshKey, (u64, CachedChunk)>,
+    order: BTreeMap<u64, ChunkMeshKey>,
+    next_stamp: u64,
+    stats: ChunkMeshCacheStats,
+}
+
+impl ChunkMeshCache {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            bytes: 0,
+            entries: HashMap::default(),
+            order: BTreeMap::new(),
+            next_stamp: 0,
+            stats: ChunkMeshCacheStats::default(),
+        }
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Approximate memory held by the cached meshes and colliders.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn stats(&self) -> ChunkMeshCacheStats {
+        self.stats
+    }
+
+    /// Caches a chunk, evicting the least recently cached chunks while over budget.
+    pub fn insert(&mut self, key: ChunkMeshKey, chunk: CachedChunk) {
+        self.remove(&key);
+        if chunk.bytes > self.budget {
+            return;
+        }
+
+        self.bytes += chunk.bytes;
+        self.order.insert(self.next_stamp, key);
+        self.entries.insert(key, (self.next_stamp, chunk));
+        self.next_stamp += 1;
+
+        while self.bytes > self.budget {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some((_, evicted)) = self.entries.remove(&oldest) {
+                self.bytes -= evicted.bytes;
+                self.stats.evictions += 1;
+            }
+        }
+    }
+
+    /// Takes a cached chunk out of the cache, counting a hit or a miss.
+    pub fn take(&mut self, key: &ChunkMeshKey) -> Option<CachedChunk> {
+        let chunk = self.remove(key);
+        match chunk {
+            Some(_) => self.stats.hits += 1,
+            None => self.stats.misses += 1,
+        }
+        chunk
+    }
+
+    /// Drops every cached chunk of `body`, for when its chunks would be generated differently.
+    pub fn remove_body(&mut self, body: Entity) {
+        let keys: Vec<ChunkMeshKey> = self
+            .entries
+            .keys()
+            .filter(|key| key.body == body)
+            .copied()
+            .collect();
+        for key in keys {
+            self.remove(&key);
+        }
+    }
+
+    fn remove(&mut self, key: &ChunkMeshKey) -> Option<CachedChunk> {
+        let (stamp, chunk) = self.entries.remove(key)?;
+        self.order.remove(&stamp);
+        self.bytes -= chunk.bytes;
+        Some(chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::terrain::biome::{Biomes, Climate};
+    use crate::plugins::terrain::cube_tree::{Axis, LodSettings};
+    use crate::plugins::terrain::height::{FractalNoise, Heightfield};
+    use crate::plugins::terrain::mesh::ChunkMeshBuilder;
+    use avian3d::math::Scalar;
+
+    fn key(axis: Axis) -> ChunkMeshKey {
+        ChunkMeshKey {
+            body: Entity::PLACEHOLDER,
+            hash: ChunkHash::new_root(axis),
+            edge_lods: EdgeLods::NONE,
+            settings: 0,
+        }
+    }
+
+    fn chunk(bytes: usize) -> CachedChunk {
+        CachedChunk {
+            mesh: Handle::default(),
+            collider: None,
+            ocean: None,
+            bytes,
+        }
+    }
+
+    #[test]
+    fn test_evicts_least_recently_cached_over_budget() {
+        let mut cache = ChunkMeshCache::new(100);
+        cache.insert(key(Axis::X), chunk(40));
+        cache.insert(key(Axis::Y), chunk(40));
+        cache.insert(key(Axis::Z), chunk(40));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.bytes(), 80);
+
+        assert!(cache.take(&key(Axis::X)).is_none());
+        assert!(cache.take(&key(Axis::Y)).is_some());
+        assert_eq!(cache.bytes(), 40);
+        assert_eq!(
+            cache.stats(),
+            ChunkMeshCacheStats {
+                hits: 1,
+                misses: 1,
+                evictions: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_misses_after_lod_settings_change() {
+        const RADIUS: Scalar = 1000.0;
+        let height = Heightfield::from(FractalNoise::EARTH);
+        let key = |settings: LodSettings| {
+            let builder = ChunkMeshBuilder::new(RADIUS, height.clone())
+                .with_subdivisions(settings.subdivisions)
+                .with_split_factor(settings.split_factor);
+            ChunkMeshKey {
+                settings: builder.settings_fingerprint(),
+                ..key(Axis::X)
+            }
+        };
+        let settings = LodSettings::for_radius(RADIUS);
+        let mut cache = ChunkMeshCache::new(100);
+        cache.insert(key(settings), chunk(40));
+
+        let subdivided = LodSettings {
+            subdivisions: settings.subdivisions * 2,
+            ..settings
+        };
+        let split = LodSettings {
+            split_factor: settings.split_factor * 2.0,
+            ..settings
+        };
+        assert!(cache.take(&key(subdivided)).is_none());
+        assert!(cache.take(&key(split)).is_none());
+        assert!(cache.take(&key(settings)).is_some());
+    }
+
+    #[test]
+    fn test_misses_after_biomes_change() {
+        let height = Heightfield::from(FractalNoise::EARTH);
+        let key = |biomes: Biomes| {
+            let builder = ChunkMeshBuilder::new(1000.0, height.clone()).with_biomes(biomes);
+            ChunkMeshKey {
+                settings: builder.settings_fingerprint(),
+                ..key(Axis::X)
+            }
+        };
+        let mut cache = ChunkMeshCache::new(100);
+        cache.insert(key(Biomes::earth()), chunk(40));
+
+        let sharper = Biomes::earth().with_transition(Climate {
+            temperature: 1.0,
+            moisture: 0.01,
+            altitude: 10.0,
+            slope: 0.01,
+        });
+        assert!(cache.take(&key(sharper)).is_none());
+        assert!(cache.take(&key(Biomes::earth())).is_some());
+    }
+}
@@ -1,11 +1,12 @@
 #![allow(warnings)]
 
-use avian3d::math::{AdjustPrecision, PI, Scalar};
+use avian3d::math::{AdjustPrecision, AsF32, Scalar, PI};
 use avian3d::{math::Vector, prelude::Collider};
 use bevy::utils::HashMap;
 use bevy::{
     ecs::world::CommandQueue,
     prelude::*,
+    render::storage::ShaderStorageBuffer,
     tasks::{block_on, poll_once, AsyncComputeTaskPool, Task},
     utils::HashSet,
 };
@@ -13,83 +14,230 @@ use big_space::grid::Grid;
 use big_space::prelude::GridCell;
 use lazy_static::lazy_static;
 
+pub mod biome;
 pub mod body;
 pub mod cube_tree;
+pub mod culling;
+pub mod disk_cache;
+pub mod height;
 pub mod helpers;
 pub mod material;
 pub mod mesh;
+pub mod mesh_cache;
+pub mod ocean;
+pub mod scheduler;
 
 #[cfg(debug_assertions)]
 mod debug;
-mod height;
 
+pub use biome::{Biome, Biomes, Climate, ClimateModel};
 pub use body::{Body, BodyPreset, Radius};
+pub use disk_cache::ChunkDiskCache;
+pub use height::{FractalNoise, HeightSource, Heightfield};
+pub use mesh_cache::{ChunkMeshCache, ChunkMeshCacheStats};
+pub use ocean::OceanMaterial;
+pub use scheduler::GenerateChunk;
 
+use crate::constants::terrain::CHUNK_SUBDIVISIONS;
 use crate::math::Rectangle;
 use crate::Precision;
 
 use body::{Chunk, ChunkCache};
-use cube_tree::{ChunkData, ChunkHash, CubeTree};
-use material::TerrainMaterials;
-use mesh::ChunkMeshBuilder;
+pub use cube_tree::LodSettings;
+use cube_tree::{ChunkData, ChunkHash, CubeTree, EdgeLods, Observer};
+use culling::{chunk_aabb, chunk_bounding_sphere, with_ocean, ChunkFrustum, Horizon};
+use material::{
+    CompactChunkRecord, CompactChunkSlots, MorphObserver, MorphObservers, TerrainCompactMaterial,
+    TerrainMaterial, TerrainMaterials, TerrainOceanMaterial, TerrainSplatMaterial,
+    TerrainStandardMaterial,
+};
+use mesh::{set_chunk_slot, ChunkMeshBuilder, EdgeMode, VertexMode};
+use mesh_cache::{CachedChunk, ChunkMeshKey};
+use ocean::{attach_ocean, OceanChunk, OceanMeshBuilder};
+use scheduler::{CancellationToken, ChunkJob, ChunkJobQueue, ChunkWork};
 
-const NORMAL_CULLING_LEEWAY_DEGREES: Scalar = 20.0;
+#[derive(Event, Copy, Clone, Default)]
+pub struct GenerateMeshes(pub Vector);
 
-lazy_static! {
-    // Pre-compute the cosine threshold once
-    static ref CHUNK_CULLING_THRESHOLD: Scalar = {
-        Scalar::cos((90.0 + NORMAL_CULLING_LEEWAY_DEGREES).to_radians())
-    };
+/// Makes an entity refine the terrain of the [`Body`] it is parented to.
+///
+/// Entities with the marker component of [`TerrainPlugin`] are observers with the default
+/// settings. `weight` scales the distance at which chunks split, and `radius` extends the
+/// fully detailed area around the entity.
+#[derive(Component, Reflect, Copy, Clone, Debug)]
+#[reflect(Component)]
+pub struct LodObserver {
+    pub weight: Scalar,
+    pub radius: Scalar,
 }
 
-#[derive(Event, Copy, Clone, Default)]
-pub struct GenerateMeshes(pub Vector);
+impl Default for LodObserver {
+    fn default() -> Self {
+        Self {
+            weight: 1.0,
+            radius: 0.0,
+        }
+    }
+}
 
 #[derive(Component)]
-pub struct GenerateChunk(pub Task<CommandQueue>);
+pub struct DespawnChunk;
 
+/// Marks a chunk that left its [`CubeTree`] through a split or merge. It stays rendered until
+/// every resident chunk covering its area has a mesh, so the surface never shows holes.
 #[derive(Component)]
-pub struct DespawnChunk;
+pub struct RetiringChunk(pub ChunkHash);
 
 #[derive(Copy, Clone, Resource)]
 pub struct TerrainPluginConfig {
-    position_threshold: Scalar,
+    /// How far, in meters, an observer must move before the terrain around it is refined.
+    pub position_threshold: Scalar,
+    /// How chunks hide the cracks between them. Compact chunks always use skirts, see
+    /// [`TerrainPluginConfig::effective_edge_mode`].
+    pub edge_mode: EdgeMode,
+    pub vertex_mode: VertexMode,
+    /// Chunk mesh resolution of bodies without their own [`LodSettings::subdivisions`].
+    pub subdivisions: usize,
+    /// Keeps neighbouring chunks within one level of each other, see [`CubeTree::balance`].
+    pub balanced: bool,
+    /// Only keeps chunks resident while they intersect the view frustum of an observing camera.
+    pub frustum_culling: bool,
+    /// Angle, in radians, by which the view frustum is widened with frustum culling. Chunks are
+    /// re-evaluated once a camera turns by half of it, so the view never reaches unbuilt chunks.
+    pub frustum_margin: Scalar,
+    /// Chunks within this distance of an observing camera stay resident outside its frustum, so
+    /// they keep casting shadows into view.
+    pub frustum_keep_distance: Scalar,
+    /// How many chunks may be built at once. Further chunks wait in the [`ChunkJobQueue`].
+    pub max_chunk_jobs: usize,
+    /// Memory, in bytes, the [`ChunkMeshCache`] may hold for despawned chunks.
+    pub mesh_cache_budget: usize,
 }
 
 impl Default for TerrainPluginConfig {
     fn default() -> Self {
         Self {
             position_threshold: 6.0,
+            edge_mode: EdgeMode::default(),
+            vertex_mode: VertexMode::default(),
+            subdivisions: CHUNK_SUBDIVISIONS,
+            balanced: true,
+            frustum_culling: false,
+            frustum_margin: 0.35,
+            frustum_keep_distance: 500.0,
+            max_chunk_jobs: 16,
+            mesh_cache_budget: 256 * 1024 * 1024,
         }
     }
 }
 
 #[derive(Default)]
-pub struct TerrainPlugin<T: Component, const SUBDIVISIONS: usize>
-where
-    [(); (SUBDIVISIONS + 2).pow(2)]:,
-    [(); (SUBDIVISIONS + 1).pow(2) * 6]:,
-{
+pub struct TerrainPlugin<T: Component> {
     cfg: TerrainPluginConfig,
     _marker: std::marker::PhantomData<T>,
 }
 
-impl<T: Component, const SUBDIVISIONS: usize> Plugin for TerrainPlugin<T, SUBDIVISIONS>
-where
-    [(); (SUBDIVISIONS + 2).pow(2)]:,
-    [(); (SUBDIVISIONS + 1).pow(2) * 6]:,
-{
+impl TerrainPluginConfig {
+    /// Returns how far, in meters, an observer at `altitude` above the body's radius may move
+    /// before the terrain around it is refined. Observers high above the surface can move
+    /// further before their chunks change.
+    pub fn observer_threshold(&self, altitude: Scalar) -> Scalar {
+        self.position_threshold.max(altitude * 0.01)
+    }
+
+    /// Returns the edge mode chunks are built with. Compact chunks are placed by their vertex
+    /// index, so they cannot stitch their edges and use skirts instead.
+    pub fn effective_edge_mode(&self) -> EdgeMode {
+        match self.vertex_mode {
+            VertexMode::Compact => EdgeMode::Skirts,
+            _ => self.edge_mode,
+        }
+    }
+}
+
+impl<T: Component> TerrainPlugin<T> {
+    pub fn with_edge_mode(mut self, edge_mode: EdgeMode) -> Self {
+        self.cfg.edge_mode = edge_mode;
+        self
+    }
+
+    pub fn with_subdivisions(mut self, subdivisions: usize) -> Self {
+        self.cfg.subdivisions = subdivisions;
+        self
+    }
+
+    pub fn with_vertex_mode(mut self, vertex_mode: VertexMode) -> Self {
+        self.cfg.vertex_mode = vertex_mode;
+        self
+    }
+
+    pub fn with_balancing(mut self, balanced: bool) -> Self {
+        self.cfg.balanced = balanced;
+        self
+    }
+
+    pub fn with_frustum_culling(mut self, frustum_culling: bool) -> Self {
+        self.cfg.frustum_culling = frustum_culling;
+        self
+    }
+
+    pub fn with_frustum_margin(mut self, frustum_margin: Scalar) -> Self {
+        self.cfg.frustum_margin = frustum_margin;
+        self
+    }
+
+    pub fn with_frustum_keep_distance(mut self, frustum_keep_distance: Scalar) -> Self {
+        self.cfg.frustum_keep_distance = frustum_keep_distance;
+        self
+    }
+
+    pub fn with_position_threshold(mut self, position_threshold: Scalar) -> Self {
+        self.cfg.position_threshold = position_threshold;
+        self
+    }
+
+    pub fn with_max_chunk_jobs(mut self, max_chunk_jobs: usize) -> Self {
+        self.cfg.max_chunk_jobs = max_chunk_jobs;
+        self
+    }
+
+    pub fn with_mesh_cache_budget(mut self, mesh_cache_budget: usize) -> Self {
+        self.cfg.mesh_cache_budget = mesh_cache_budget;
+        self
+    }
+}
+
+impl<T: Component> Plugin for TerrainPlugin<T> {
     fn build(&self, app: &mut App) {
         app.insert_resource(self.cfg)
+            .add_plugins(MaterialPlugin::<TerrainStandardMaterial>::default())
+            .add_plugins(MaterialPlugin::<TerrainCompactMaterial>::default())
+            .add_plugins(MaterialPlugin::<TerrainSplatMaterial>::default())
+            .add_plugins(MaterialPlugin::<TerrainOceanMaterial>::default())
             .init_resource::<TerrainMaterials>()
-            .add_observer(generate_meshes::<SUBDIVISIONS>)
+            .init_resource::<ChunkJobQueue>()
+            .insert_resource(ChunkMeshCache::new(self.cfg.mesh_cache_budget))
+            .register_type::<LodSettings>()
+            .register_type::<LodObserver>()
+            .register_type::<Biomes>()
+            .add_observer(generate_meshes::<T>)
             .add_systems(
                 Update,
                 (
+                    dispatch_chunk_jobs,
                     handle_chunk_generation_tasks,
+                    handle_retiring_chunks,
                     handle_despawn_chunks,
-                    track_target_position::<T>,
+                    track_observers::<T>,
+                    apply_lod_settings,
+                    apply_biomes,
+                    refresh_frustum_culling::<T>,
+                    upload_compact_chunks,
                 ),
+            )
+            .add_systems(
+                PostUpdate,
+                update_morph_observers.after(TransformSystem::TransformPropagate),
             );
 
         #[cfg(debug_assertions)]
@@ -101,12 +249,20 @@ where
 }
 
 #[allow(clippy::type_complexity)]
-fn track_target_position<T: Component>(
+fn track_observers<T: Component>(
     mut commands: Commands,
-    target_query: Query<(&GridCell<i64>, &Transform, &Parent), With<T>>,
-    mut planet_query: Query<
+    observer_query: Query<
         (
             Entity,
+            &GridCell<i64>,
+            &Transform,
+            &Parent,
+            Option<&LodObserver>,
+        ),
+        Or<(With<T>, With<LodObserver>)>,
+    >,
+    mut planet_query: Query<
+        (
             &Radius,
             &Grid<i64>,
             &GridCell<i64>,
@@ -115,87 +271,330 @@ fn track_target_position<T: Component>(
         ),
         With<Body>,
     >,
-    mut prev_position: Local<Vector>,
+    config: Res<TerrainPluginConfig>,
+    mut prev_positions: Local<HashMap<Entity, (Entity, Vector)>>,
 ) {
-    let Ok((target_cell, target_transform, parent)) = target_query.get_single() else {
-        return;
-    };
-    let Ok((entity, radius, grid, planet_cell, planet_transform, mut cube_tree)) =
-        planet_query.get_mut(parent.get())
-    else {
-        return;
-    };
-    let target_position = grid
-        .grid_position_double(target_cell, target_transform)
-        .adjust_precision();
-    let planet_position = grid
-        .grid_position_double(planet_cell, planet_transform)
-        .adjust_precision();
-    let relative_pos = target_position - planet_position;
-    if target_position.distance(*prev_position)
-        < (target_position.distance(planet_position) - **radius) * 0.01
-    {
+    let mut observers: HashMap<Entity, Vec<Observer>> = HashMap::new();
+    let mut moved: HashSet<Entity> = HashSet::new();
+    for (entity, cell, transform, parent, lod_observer) in observer_query.iter() {
+        let body = parent.get();
+        let Ok((radius, grid, planet_cell, planet_transform, _)) = planet_query.get(body) else {
+            continue;
+        };
+        let target_position = grid
+            .grid_position_double(cell, transform)
+            .adjust_precision();
+        let planet_position = grid
+            .grid_position_double(planet_cell, planet_transform)
+            .adjust_precision();
+        let lod_observer = lod_observer.copied().unwrap_or_default();
+        observers.entry(body).or_default().push(
+            Observer::new(target_position - planet_position)
+                .with_weight(lod_observer.weight)
+                .with_radius(lod_observer.radius),
+        );
+
+        let (prev_body, prev_position) =
+            prev_positions.entry(entity).or_insert((body, Vector::MAX));
+        let threshold =
+            config.observer_threshold(target_position.distance(planet_position) - **radius);
+        if *prev_body != body || target_position.distance(*prev_position) >= threshold {
+            moved.insert(*prev_body);
+            moved.insert(body);
+            *prev_body = body;
+            *prev_position = target_position;
+        }
+    }
+    // Observers that went away no longer hold detail around them.
+    prev_positions.retain(|entity, (body, _)| {
+        let exists = observer_query.contains(*entity);
+        if !exists {
+            moved.insert(*body);
+        }
+        exists
+    });
+
+    for body in moved {
+        let Ok((_, _, _, _, mut cube_tree)) = planet_query.get_mut(body) else {
+            continue;
+        };
+        let observers = observers.remove(&body).unwrap_or_default();
+        cube_tree.update(&observers);
+        let target_position = observers
+            .first()
+            .map_or(Vector::MAX, |observer| observer.position);
+        commands
+            .entity(body)
+            .trigger(GenerateMeshes(target_position));
+    }
+}
+
+fn apply_lod_settings(
+    mut commands: Commands,
+    mut mesh_cache: ResMut<ChunkMeshCache>,
+    mut query: Query<(Entity, &LodSettings, &mut CubeTree, &mut ChunkCache), Changed<LodSettings>>,
+) {
+    for (entity, settings, mut cube_tree, mut chunk_cache) in query.iter_mut() {
+        let settings = settings.clamped();
+        if cube_tree.settings == settings {
+            continue;
+        }
+        // Meshes depend on the resolution and geomorph for the split factor, so every chunk is
+        // rebuilt. The old ones stay until their replacements are meshed, and are not cached
+        // when they despawn.
+        if cube_tree.settings.subdivisions != settings.subdivisions
+            || cube_tree.settings.split_factor != settings.split_factor
+        {
+            mesh_cache.remove_body(entity);
+            for (hash, chunk_entity) in chunk_cache.drain() {
+                commands
+                    .entity(chunk_entity)
+                    .remove::<GenerateChunk>()
+                    .remove::<ChunkMeshKey>()
+                    .insert(RetiringChunk(hash));
+            }
+        }
+        cube_tree.settings = settings;
+        cube_tree.refresh();
+        commands.entity(entity).trigger(GenerateMeshes(Vector::MAX));
+    }
+}
+
+/// Hands the observers of every [`CubeTree`] to the terrain materials, so chunks geomorph by the
+/// same distances their trees split and merge at.
+fn update_morph_observers(
+    body_query: Query<(&GlobalTransform, &CubeTree), With<Body>>,
+    mut standard_materials: ResMut<Assets<TerrainStandardMaterial>>,
+    mut splat_materials: ResMut<Assets<TerrainSplatMaterial>>,
+    mut ocean_materials: ResMut<Assets<TerrainOceanMaterial>>,
+    mut prev_observers: Local<MorphObservers>,
+) {
+    // Tree observers are relative to their body, along the axes of the grid.
+    let observers = MorphObservers::new(body_query.iter().flat_map(|(transform, cube_tree)| {
+        cube_tree
+            .observers()
+            .iter()
+            .map(|observer| MorphObserver {
+                position: transform.translation() + observer.position.f32(),
+                radius: observer.radius as f32,
+                weight: observer.weight as f32,
+            })
+            .filter(|observer| observer.position.is_finite())
+    }));
+    if observers == *prev_observers {
         return;
     }
-    *prev_position = target_position;
-    cube_tree.insert(relative_pos);
-    commands
-        .entity(entity)
-        .trigger(GenerateMeshes(relative_pos));
+    *prev_observers = observers;
+
+    for (_, material) in standard_materials.iter_mut() {
+        material.extension.observers = observers;
+    }
+    for (_, material) in splat_materials.iter_mut() {
+        material.extension.observers = observers;
+    }
+    for (_, material) in ocean_materials.iter_mut() {
+        material.extension.observers = observers;
+    }
+}
+
+/// Rebuilds the chunks of bodies whose [`Biomes`] changed, and gives them a material with the new
+/// biome colors.
+fn apply_biomes(
+    mut commands: Commands,
+    mut mesh_cache: ResMut<ChunkMeshCache>,
+    mut standard_materials: ResMut<Assets<TerrainStandardMaterial>>,
+    mut compact_materials: ResMut<Assets<TerrainCompactMaterial>>,
+    mut query: Query<(
+        Entity,
+        Ref<Biomes>,
+        &TerrainMaterial,
+        &mut ChunkCache,
+        Option<&CompactChunkSlots>,
+    )>,
+) {
+    for (entity, biomes, terrain_material, mut chunk_cache, compact_slots) in query.iter_mut() {
+        if !biomes.is_changed() || biomes.is_added() {
+            continue;
+        }
+        // The old chunks carry the old biome weights, so they are not cached when they despawn.
+        mesh_cache.remove_body(entity);
+        for (hash, chunk_entity) in chunk_cache.drain() {
+            commands
+                .entity(chunk_entity)
+                .remove::<GenerateChunk>()
+                .remove::<ChunkMeshKey>()
+                .insert(RetiringChunk(hash));
+        }
+        // The material may be shared with other bodies, so the new colors go into a copy.
+        if let Some(material) = terrain_material
+            .standard()
+            .and_then(|handle| standard_materials.get(handle))
+        {
+            let material = TerrainStandardMaterial {
+                base: material.base.clone(),
+                extension: material.extension.clone().with_biomes(&biomes),
+            };
+            commands
+                .entity(entity)
+                .insert(TerrainMaterial::Standard(standard_materials.add(material)));
+        }
+        // The compact material belongs to the body alone.
+        if let Some(slots) = compact_slots {
+            if let Some(material) = compact_materials.get_mut(&slots.material) {
+                material.extension.biome_colors = biomes.colors();
+            }
+        }
+        commands.entity(entity).trigger(GenerateMeshes(Vector::MAX));
+    }
 }
 
 #[allow(clippy::type_complexity)]
-fn generate_meshes<const SUBDIVISIONS: usize>(
+fn generate_meshes<T: Component>(
     trigger: Trigger<GenerateMeshes>,
     mut commands: Commands,
+    config: Res<TerrainPluginConfig>,
+    mut job_queue: ResMut<ChunkJobQueue>,
+    mut mesh_cache: ResMut<ChunkMeshCache>,
+    disk_cache: Option<Res<ChunkDiskCache>>,
+    mut meshes: ResMut<Assets<Mesh>>,
     mut planet_query: Query<
         (
-            &CubeTree,
+            &mut CubeTree,
             &Grid<Precision>,
             &GridCell<Precision>,
             &Transform,
+            &GlobalTransform,
             &Radius,
             &mut ChunkCache,
+            &Body,
+            Option<&Biomes>,
+            Option<&OceanMaterial>,
+            Option<&mut CompactChunkSlots>,
         ),
         With<Body>,
     >,
-) where
-    [(); (SUBDIVISIONS + 2).pow(2)]:,
-    [(); (SUBDIVISIONS + 1).pow(2) * 6]:,
-{
+    camera_query: Query<(&Camera, &GlobalTransform), Or<(With<T>, With<LodObserver>)>>,
+) {
     let target_position = trigger.0;
     let entity = trigger.entity();
-    let thread_pool = AsyncComputeTaskPool::get();
 
-    for (cube_tree, grid, grid_cell, transform, radius, mut chunk_cache) in planet_query.iter_mut()
-    {
-        let filtered_chunks: Vec<(&Rectangle, &ChunkData)> = cube_tree
-            .iter()
-            .filter(|(bounds, data)| {
-                let vector_to_target = target_position - data.center;
-                if vector_to_target.length_squared() < 1e-6 {
-                    return true;
-                }
-                data.center.normalize().dot(vector_to_target.normalize()) > *CHUNK_CULLING_THRESHOLD
-            })
-            .collect();
-        let mut hash_set: HashSet<ChunkHash> =
-            HashSet::from_iter(filtered_chunks.iter().map(|(_, data)| data.hash));
+    let Ok((
+        mut cube_tree,
+        grid,
+        grid_cell,
+        transform,
+        global_transform,
+        radius,
+        mut chunk_cache,
+        body,
+        biomes,
+        ocean_material,
+        mut compact_slots,
+    )) = planet_query.get_mut(entity)
+    else {
+        return;
+    };
 
-        for (_, entity) in chunk_cache.extract_if(|bounds, _| !hash_set.contains(bounds)) {
-            commands.entity(entity).insert(DespawnChunk);
+    let changes = cube_tree.take_changes();
+    for hash in changes.removed.iter() {
+        if let Some(chunk_entity) = chunk_cache.remove(hash) {
+            commands
+                .entity(chunk_entity)
+                .remove::<GenerateChunk>()
+                .insert(RetiringChunk(*hash));
         }
+    }
 
-        let planet_pos = (grid as &Grid<Precision>).grid_position_double(grid_cell, transform);
-        let mesh_builder = ChunkMeshBuilder::<SUBDIVISIONS>::new(radius.0);
+    // Chunks are only resident while they face an observer and, with frustum culling, are
+    // inside a camera's view.
+    // Nothing lies below the lowest chunk, so that sphere occludes whatever is behind it.
+    let min_elevation = cube_tree
+        .iter()
+        .map(|(_, data)| data.min_elevation)
+        .fold(Scalar::INFINITY, Scalar::min);
+    let occluder_radius = (radius.0 + min_elevation).max(0.0);
+    let horizons: Vec<Horizon> = match cube_tree.observers() {
+        [] => vec![Horizon::new(target_position, occluder_radius)],
+        observers => observers
+            .iter()
+            .map(|observer| Horizon::new(observer.position, occluder_radius))
+            .collect(),
+    };
+    let frustums: Vec<ChunkFrustum> = camera_query
+        .iter()
+        .filter(|(camera, _)| config.frustum_culling && camera.is_active)
+        .map(|(camera, camera_transform)| {
+            let frustum = ChunkFrustum::new(camera, camera_transform, global_transform);
+            // The camera may turn by the margin and move by its observer threshold before the
+            // frustum is tested again.
+            let threshold = config.observer_threshold(frustum.position().length() - radius.0);
+            frustum
+                .with_margin(config.frustum_margin, threshold)
+                .with_keep_distance(config.frustum_keep_distance)
+        })
+        .collect();
+    // Oceans rise above the seabed, so chunks are visible as long as their water is.
+    let is_visible = |data: &ChunkData| {
+        let data = &with_ocean(data, body.sea_level);
+        let (center, bounding_radius) = chunk_bounding_sphere(data, radius.0);
+        horizons
+            .iter()
+            .any(|horizon| horizon.is_visible(data, radius.0))
+            && (frustums.is_empty()
+                || frustums
+                    .iter()
+                    .any(|frustum| frustum.intersects_sphere(center, bounding_radius)))
+    };
 
-        for (&bounds, &data) in filtered_chunks.iter() {
-            if chunk_cache.contains_key(&data.hash) {
-                continue;
+    // Edge LODs only change the mesh when stitching, so skirted chunks ignore them.
+    let edge_mode = config.effective_edge_mode();
+    let remeshed = match edge_mode {
+        EdgeMode::Stitch => changes.edge_lods_changed,
+        EdgeMode::Skirts => HashSet::default(),
+    };
+
+    let mut builds: Vec<(Rectangle, ChunkData)> = Vec::new();
+    for (bounds, data) in cube_tree.iter() {
+        let visible = is_visible(data);
+        match chunk_cache.get(&data.hash) {
+            Some(&chunk_entity) if !visible => {
+                chunk_cache.remove(&data.hash);
+                commands.entity(chunk_entity).insert(DespawnChunk);
             }
+            Some(_) if remeshed.contains(&data.hash) => builds.push((*bounds, *data)),
+            None if visible => builds.push((*bounds, *data)),
+            _ => {}
+        }
+    }
 
+    let planet_pos = (grid as &Grid<Precision>).grid_position_double(grid_cell, transform);
+    let mut mesh_builder = ChunkMeshBuilder::new(radius.0, cube_tree.height.clone())
+        .with_subdivisions(cube_tree.settings.subdivisions)
+        .with_edge_mode(edge_mode)
+        .with_split_factor(cube_tree.settings.split_factor)
+        .with_sea_level(body.sea_level.unwrap_or(0.0));
+    if let Some(biomes) = biomes {
+        mesh_builder = mesh_builder.with_biomes(biomes.clone());
+    }
+    // Oceans follow the same tree, over the chunks that reach below sea level.
+    let ocean = body
+        .sea_level
+        .zip(ocean_material)
+        .map(|(sea_level, material)| {
+            let builder = OceanMeshBuilder::new(radius.0, sea_level, cube_tree.height.clone())
+                .with_subdivisions(cube_tree.settings.subdivisions)
+                .with_edge_mode(edge_mode)
+                .with_split_factor(cube_tree.settings.split_factor);
+            (builder, material.0.clone())
+        });
+    let disk_cache = disk_cache
+        .map(|disk_cache| disk_cache.clone())
+        .zip(mesh_builder.fingerprint());
+    let settings_fingerprint = mesh_builder.settings_fingerprint();
+    for (bounds, data) in builds {
+        let chunk_entity = *chunk_cache.entry(data.hash).or_insert_with(|| {
             let (grid_cell, translation) = grid.translation_to_grid(data.center - planet_pos);
-            let chunk_entity = commands
+            commands
                 .spawn((
                     grid_cell,
                     Transform::from_translation(translation),
@@ -203,57 +602,312 @@ fn generate_meshes<const SUBDIVISIONS: usize>(
                 ))
                 .set_parent(entity)
                 .insert(Chunk)
-                .id();
-
-            chunk_cache.insert(data.hash, chunk_entity);
+                .id()
+        });
 
-            let has_collider = data.hash.collider();
-            let task = thread_pool.spawn(async move {
-                let mut command_queue = CommandQueue::default();
+        let key = ChunkMeshKey {
+            body: entity,
+            hash: data.hash,
+            edge_lods: match edge_mode {
+                EdgeMode::Stitch => data.edge_lods,
+                EdgeMode::Skirts => EdgeLods::NONE,
+            },
+            settings: settings_fingerprint,
+        };
+        // Compact chunks find where they lie through their slot in the body's compact material.
+        let compact = compact_slots
+            .as_deref_mut()
+            .filter(|_| config.vertex_mode == VertexMode::Compact)
+            .map(|slots| {
+                let record = CompactChunkRecord::new(
+                    data.hash.axis(),
+                    &bounds,
+                    radius.0,
+                    data.center,
+                    mesh_builder.vertex_count(),
+                );
+                let slot = slots.insert(chunk_entity, record);
+                let material = MeshMaterial3d(slots.material.clone());
+                (slot, (material, chunk_aabb(&data, radius.0)))
+            });
 
-                let mesh = mesh_builder.build(&bounds, &data);
-                let collider = has_collider.then(|| {
-                    Collider::trimesh_from_mesh(&mesh)
-                        .expect("expected collider construction to succeed")
+        if let Some(cached) = mesh_cache.take(&key) {
+            job_queue.remove(chunk_entity);
+            // The chunk may come back with another slot than its mesh was built with.
+            if let Some((slot, _)) = compact {
+                if let Some(mesh) = meshes.get_mut(&cached.mesh) {
+                    set_chunk_slot(mesh, slot);
+                }
+            }
+            let mut chunk_commands = commands.entity(chunk_entity);
+            chunk_commands
+                .remove::<GenerateChunk>()
+                .insert((Mesh3d(cached.mesh), key));
+            if let Some(collider) = cached.collider {
+                chunk_commands.insert(collider);
+            }
+            if let Some((_, compact)) = compact {
+                chunk_commands
+                    .remove::<MeshMaterial3d<TerrainStandardMaterial>>()
+                    .remove::<MeshMaterial3d<TerrainSplatMaterial>>()
+                    .insert(compact);
+            }
+            if let Some((ocean_mesh, (_, material))) = cached.ocean.zip(ocean.clone()) {
+                commands.queue(move |world: &mut World| {
+                    attach_ocean(world, chunk_entity, ocean_mesh, material);
                 });
+            }
+            continue;
+        }
+
+        let has_collider = data.hash.collider();
+        let mesh_builder = mesh_builder.clone();
+        let disk_cache = disk_cache.clone();
+        let ocean = ocean.clone();
+        let work: ChunkWork = Box::new(move |cancellation| {
+            let mesh = match &disk_cache {
+                Some((disk_cache, fingerprint)) => {
+                    disk_cache.load_or_build(*fingerprint, data.hash, key.edge_lods, || {
+                        mesh_builder.build(&bounds, &data)
+                    })
+                }
+                None => mesh_builder.build(&bounds, &data),
+            };
+            if cancellation.is_cancelled() {
+                return None;
+            }
+            let collider = has_collider.then(|| {
+                Collider::trimesh_from_mesh(&mesh)
+                    .expect("expected collider construction to succeed")
+            });
+            if cancellation.is_cancelled() {
+                return None;
+            }
+            let mesh = match &compact {
+                Some((slot, _)) => mesh_builder.compact(&mesh, &data, *slot),
+                None => mesh,
+            };
+            let ocean = ocean
+                .and_then(|(builder, material)| Some((builder.build(&bounds, &data)?, material)));
+
+            let mut command_queue = CommandQueue::default();
+            command_queue.push(move |world: &mut World| {
+                let mesh_handle = world
+                    .get_resource_mut::<Assets<Mesh>>()
+                    .expect("expected Assets<Mesh> resource to exist")
+                    .add(mesh);
 
-                command_queue.push(move |world: &mut World| {
-                    let mesh_handle = world
-                        .get_resource_mut::<Assets<Mesh>>()
-                        .expect("expected Assets<Mesh> resource to exist")
-                        .add(mesh);
-
-                    if let Ok(mut entity_mut) = world.get_entity_mut(chunk_entity) {
-                        match collider {
-                            Some(collider) => entity_mut.insert((
-                                collider,
-                                Mesh3d(mesh_handle),
-                            )),
-                            None => entity_mut.insert(Mesh3d(mesh_handle)),
-                        };
+                if let Ok(mut entity_mut) = world.get_entity_mut(chunk_entity) {
+                    match collider {
+                        Some(collider) => entity_mut.insert((collider, Mesh3d(mesh_handle), key)),
+                        None => entity_mut.insert((Mesh3d(mesh_handle), key)),
+                    };
+                    if let Some((_, compact)) = compact {
+                        entity_mut
+                            .remove::<MeshMaterial3d<TerrainStandardMaterial>>()
+                            .remove::<MeshMaterial3d<TerrainSplatMaterial>>()
+                            .insert(compact);
                     }
-                });
-                command_queue
+                }
+                if let Some((ocean_mesh, material)) = ocean {
+                    let ocean_handle = world.resource_mut::<Assets<Mesh>>().add(ocean_mesh);
+                    attach_ocean(world, chunk_entity, ocean_handle, material);
+                }
             });
-            commands.entity(chunk_entity).insert(GenerateChunk(task));
+            Some(command_queue)
+        });
+        job_queue.push(
+            chunk_entity,
+            ChunkJob::new(entity, data.hash, data.center, bounds.size().x, work),
+        );
+    }
+}
+
+/// Uploads the [`CompactChunkRecord`]s of bodies whose compact chunks changed to their compact
+/// material.
+fn upload_compact_chunks(
+    query: Query<&CompactChunkSlots, Changed<CompactChunkSlots>>,
+    mut compact_materials: ResMut<Assets<TerrainCompactMaterial>>,
+    mut buffers: ResMut<Assets<ShaderStorageBuffer>>,
+) {
+    for slots in query.iter() {
+        // Touching the material rebuilds its bind group around the new buffer.
+        let Some(material) = compact_materials.get_mut(&slots.material) else {
+            continue;
+        };
+        let Some(buffer) = buffers.get_mut(&material.extension.chunks) else {
+            continue;
+        };
+        // Storage buffers cannot be empty.
+        match slots.records() {
+            [] => buffer.set_data(vec![CompactChunkRecord::default()]),
+            records => buffer.set_data(records.to_vec()),
+        }
+    }
+}
+
+/// Re-evaluates which chunks are resident once an observing camera has turned by half the
+/// [`TerrainPluginConfig::frustum_margin`] relative to a body. Cameras that move refine the
+/// terrain through `track_observers`, which re-evaluates it as well.
+#[allow(clippy::type_complexity)]
+fn refresh_frustum_culling<T: Component>(
+    mut commands: Commands,
+    config: Res<TerrainPluginConfig>,
+    camera_query: Query<(Entity, &Camera, &GlobalTransform), Or<(With<T>, With<LodObserver>)>>,
+    body_query: Query<(Entity, &GlobalTransform), With<Body>>,
+    mut refreshed_rotations: Local<HashMap<(Entity, Entity), Quat>>,
+) {
+    if !config.frustum_culling {
+        return;
+    }
+    let threshold = (config.frustum_margin / 2.0) as f32;
+    let mut refreshed: HashSet<Entity> = HashSet::new();
+    for (camera, _, camera_transform) in camera_query
+        .iter()
+        .filter(|(_, camera, _)| camera.is_active)
+    {
+        for (body, body_transform) in body_query.iter() {
+            let rotation = body_transform.rotation().inverse() * camera_transform.rotation();
+            let refreshed_rotation = refreshed_rotations
+                .entry((camera, body))
+                .or_insert(rotation);
+            if refreshed_rotation.angle_between(rotation) > threshold {
+                *refreshed_rotation = rotation;
+                refreshed.insert(body);
+            }
         }
     }
+    refreshed_rotations
+        .retain(|(camera, body), _| camera_query.contains(*camera) && body_query.contains(*body));
+
+    for body in refreshed {
+        commands.entity(body).trigger(GenerateMeshes(Vector::MAX));
+    }
+}
+
+/// Starts the most urgent queued chunk jobs while fewer than
+/// [`TerrainPluginConfig::max_chunk_jobs`] are running, dropping jobs whose chunks were evicted.
+fn dispatch_chunk_jobs(
+    mut commands: Commands,
+    config: Res<TerrainPluginConfig>,
+    mut job_queue: ResMut<ChunkJobQueue>,
+    running_query: Query<(), With<GenerateChunk>>,
+    body_query: Query<(&CubeTree, &ChunkCache), With<Body>>,
+) {
+    if job_queue.is_empty() {
+        return;
+    }
+
+    job_queue.retain(|chunk, job| {
+        body_query
+            .get(job.body)
+            .is_ok_and(|(_, chunk_cache)| chunk_cache.get(&job.hash) == Some(&chunk))
+    });
+
+    let free = config
+        .max_chunk_jobs
+        .saturating_sub(running_query.iter().count());
+    let jobs = job_queue.pop_highest(free, |job| {
+        body_query
+            .get(job.body)
+            .map_or(0.0, |(cube_tree, _)| job.priority(cube_tree.observers()))
+    });
+
+    let thread_pool = AsyncComputeTaskPool::get();
+    for (chunk, job) in jobs {
+        let cancellation = CancellationToken::default();
+        let task = thread_pool.spawn({
+            let cancellation = cancellation.clone();
+            async move { job.run(&cancellation) }
+        });
+        commands
+            .entity(chunk)
+            .try_insert(GenerateChunk::new(task, cancellation));
+    }
 }
 
 fn handle_chunk_generation_tasks(
     mut commands: Commands,
     mut tasks: Query<(Entity, &mut GenerateChunk), With<Chunk>>,
 ) {
-    for (entity, mut task) in tasks.iter_mut() {
-        if let Some(mut commands_queue) = block_on(poll_once(&mut task.0)) {
-            commands.append(&mut commands_queue);
+    for (entity, mut generate_chunk) in tasks.iter_mut() {
+        if let Some(result) = block_on(poll_once(&mut generate_chunk.task)) {
+            if let Some(mut commands_queue) = result {
+                commands.append(&mut commands_queue);
+            }
             commands.entity(entity).remove::<GenerateChunk>();
         }
     }
 }
 
-fn handle_despawn_chunks(mut commands: Commands, mut query: Query<Entity, With<DespawnChunk>>) {
-    for entity in query.iter_mut() {
-        commands.entity(entity).remove_parent().despawn();
+/// Despawns retiring chunks once the chunks that replaced them are meshed. Retiring chunks that
+/// never got a mesh go right away.
+fn handle_retiring_chunks(
+    mut commands: Commands,
+    retiring_query: Query<(Entity, &RetiringChunk, &Parent, Has<Mesh3d>), Without<DespawnChunk>>,
+    body_query: Query<(&CubeTree, &ChunkCache), With<Body>>,
+    meshed_query: Query<(), With<Mesh3d>>,
+) {
+    for (entity, RetiringChunk(hash), parent, has_mesh) in retiring_query.iter() {
+        let replaced = !has_mesh
+            || body_query
+                .get(parent.get())
+                .map_or(true, |(cube_tree, chunk_cache)| {
+                    cube_tree
+                        .leaves_overlapping(*hash)
+                        .into_iter()
+                        .filter_map(|(_, data)| chunk_cache.get(&data.hash))
+                        .all(|chunk_entity| meshed_query.contains(*chunk_entity))
+                });
+        if replaced {
+            commands.entity(entity).insert(DespawnChunk);
+        }
+    }
+}
+
+/// Despawns chunks and their oceans, keeping their meshes and colliders in the
+/// [`ChunkMeshCache`] in case they come back into view.
+#[allow(clippy::type_complexity)]
+fn handle_despawn_chunks(
+    mut commands: Commands,
+    mut mesh_cache: ResMut<ChunkMeshCache>,
+    meshes: Res<Assets<Mesh>>,
+    query: Query<
+        (
+            Entity,
+            Option<&Mesh3d>,
+            Option<&Collider>,
+            Option<&ChunkMeshKey>,
+            Option<&Children>,
+            Option<&Parent>,
+        ),
+        With<DespawnChunk>,
+    >,
+    ocean_query: Query<&Mesh3d, With<OceanChunk>>,
+    mut slots_query: Query<&mut CompactChunkSlots>,
+) {
+    for (entity, mesh_handle, collider, key, children, parent) in query.iter() {
+        // Freed slots keep their records, so nothing needs uploading until they are reused.
+        if let Some(parent) = parent {
+            if let Ok(mut slots) = slots_query.get_mut(parent.get()) {
+                slots.bypass_change_detection().remove(entity);
+            }
+        }
+        if let (Some(Mesh3d(mesh_handle)), Some(key)) = (mesh_handle, key) {
+            if let Some(mesh) = meshes.get(mesh_handle) {
+                let mut cached = CachedChunk::new(mesh_handle.clone(), mesh, collider.cloned());
+                let ocean = children
+                    .into_iter()
+                    .flatten()
+                    .find_map(|&child| ocean_query.get(child).ok());
+                if let Some(Mesh3d(ocean_handle)) = ocean {
+                    if let Some(ocean_mesh) = meshes.get(ocean_handle) {
+                        cached = cached.with_ocean(ocean_handle.clone(), ocean_mesh);
+                    }
+                }
+                mesh_cache.insert(*key, cached);
+            }
+        }
+        commands.entity(entity).despawn_recursive();
     }
 }
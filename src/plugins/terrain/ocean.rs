@@ -0,0 +1,185 @@
+use avian3d::math::{Scalar, Vector};
+use bevy::{pbr::NotShadowCaster, prelude::*, render::mesh::VertexAttributeValues};
+use std::hash::Hasher;
+
+use super::cube_tree::ChunkData;
+use super::disk_cache::FingerprintHasher;
+use super::height::{HeightSource, Heightfield};
+use super::material::TerrainOceanMaterial;
+use super::mesh::{
+    ChunkMeshBuilder, EdgeMode, ATTRIBUTE_ALTITUDE_SLOPE, ATTRIBUTE_OCEAN_DEPTH,
+    ATTRIBUTE_TEXTURE_POSITION,
+};
+use crate::math::Rectangle;
+
+/// The material of a body's ocean chunks. Bodies with a [`Body::sea_level`](super::Body) get
+/// the default one when added.
+#[derive(Component, Clone, PartialEq, Eq, Debug)]
+pub struct OceanMaterial(pub Handle<TerrainOceanMaterial>);
+
+/// The ocean surface over a terrain [`Chunk`](super::body::Chunk), spawned as its child.
+#[derive(Component, Debug)]
+#[require(Name(|| Name::new("Ocean")), Transform, Visibility, NotShadowCaster)]
+pub struct OceanChunk;
+
+/// The ocean surface as a height source, at the same elevation everywhere.
+struct SeaSurface(Scalar);
+
+impl HeightSource for SeaSurface {
+    fn height(&self, _direction: Vector) -> Scalar {
+        self.0
+    }
+
+    fn max_variation(&self, _distance: Scalar) -> Option<Scalar> {
+        Some(0.0)
+    }
+
+    fn fingerprint(&self) -> Option<u64> {
+        let mut hasher = FingerprintHasher::default();
+        hasher.write_u64(self.0.to_bits() as u64);
+        Some(hasher.finish())
+    }
+}
+
+/// Builds the ocean chunks of a body, one over each terrain chunk of its
+/// [`CubeTree`](super::cube_tree::CubeTree).
+///
+/// Ocean chunks are spheres at sea level with the same grid, geomorphing and edges as the
+/// terrain chunks below them, and store the depth of the terrain below each vertex in
+/// [`ATTRIBUTE_OCEAN_DEPTH`]. Their positions are relative to the terrain chunk's center.
+#[derive(Clone, Debug)]
+pub struct OceanMeshBuilder {
+    surface: ChunkMeshBuilder,
+    terrain: Heightfield,
+    sea_level: Scalar,
+}
+
+impl OceanMeshBuilder {
+    pub fn new(radius: Scalar, sea_level: Scalar, terrain: Heightfield) -> Self {
+        Self {
+            surface: ChunkMeshBuilder::new(radius, Heightfield::new(SeaSurface(sea_level)))
+                .with_sea_level(sea_level),
+            terrain,
+            sea_level,
+        }
+    }
+
+    pub fn with_subdivisions(mut self, subdivisions: usize) -> Self {
+        self.surface = self.surface.with_subdivisions(subdivisions);
+        self
+    }
+
+    pub fn with_edge_mode(mut self, edge_mode: EdgeMode) -> Self {
+        self.surface = self.surface.with_edge_mode(edge_mode);
+        self
+    }
+
+    pub fn with_split_factor(mut self, split_factor: Scalar) -> Self {
+        self.surface = self.surface.with_split_factor(split_factor);
+        self
+    }
+
+    /// Builds the ocean over a terrain chunk, or returns `None` if the chunk lies wholly above
+    /// sea level.
+    pub fn build(&self, bounds: &Rectangle, chunk_data: &ChunkData) -> Option<Mesh> {
+        if chunk_data.min_elevation > self.sea_level {
+            return None;
+        }
+
+        let mut mesh = self.surface.build(bounds, chunk_data);
+        mesh.remove_attribute(ATTRIBUTE_ALTITUDE_SLOPE);
+        mesh.remove_attribute(ATTRIBUTE_TEXTURE_POSITION);
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("expected ocean mesh to have positions");
+        };
+        let depths: Vec<f32> = positions
+            .iter()
+            .map(|position| {
+                let direction = (Vector::from_array(position.map(|v| v as Scalar))
+                    + chunk_data.center)
+                    .normalize();
+                (self.sea_level - self.terrain.height(direction)) as f32
+            })
+            .collect();
+        mesh.insert_attribute(ATTRIBUTE_OCEAN_DEPTH, depths);
+        Some(mesh)
+    }
+}
+
+/// Gives `chunk` an ocean with `mesh`, replacing the ocean it already has.
+pub(crate) fn attach_ocean(
+    world: &mut World,
+    chunk: Entity,
+    mesh: Handle<Mesh>,
+    material: Handle<TerrainOceanMaterial>,
+) {
+    if world.get_entity(chunk).is_err() {
+        return;
+    }
+    let ocean = world.get::<Children>(chunk).and_then(|children| {
+        children
+            .iter()
+            .copied()
+            .find(|&child| world.get::<OceanChunk>(child).is_some())
+    });
+    match ocean {
+        Some(ocean) => {
+            world
+                .entity_mut(ocean)
+                .insert((Mesh3d(mesh), MeshMaterial3d(material)));
+        }
+        None => {
+            world
+                .spawn((OceanChunk, Mesh3d(mesh), MeshMaterial3d(material)))
+                .set_parent(chunk);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::terrain::cube_tree::{Axis, ChunkHash};
+    use crate::plugins::terrain::FractalNoise;
+    use avian3d::math::Vector2;
+
+    #[test]
+    fn test_ocean_lies_at_sea_level_over_submerged_terrain() {
+        let radius = 1000.0;
+        let sea_level = 10.0;
+        let terrain = Heightfield::from(FractalNoise {
+            amplitude: 50.0,
+            ..FractalNoise::EARTH
+        });
+        let builder =
+            OceanMeshBuilder::new(radius, sea_level, terrain.clone()).with_subdivisions(4);
+        let axis = Axis::X;
+        let bounds = Rectangle::from_corners(Vector2::splat(-radius), Vector2::ZERO);
+        let data = ChunkData::new(axis, &bounds, radius, &*terrain, ChunkHash::new_root(axis));
+        let mesh = builder
+            .build(&bounds, &data)
+            .expect("expected chunk to be submerged");
+
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("expected positions");
+        };
+        let Some(VertexAttributeValues::Float32(depths)) = mesh.attribute(ATTRIBUTE_OCEAN_DEPTH)
+        else {
+            panic!("expected depths");
+        };
+        for (position, &depth) in positions.iter().zip(depths) {
+            let position = Vector::from_array(position.map(|v| v as Scalar)) + data.center;
+            assert!((position.length() - radius - sea_level).abs() < 1e-2);
+            let expected = sea_level - terrain.height(position.normalize());
+            assert!((expected - depth as Scalar).abs() < 1e-3);
+        }
+
+        // Terrain wholly above the water gets no ocean.
+        let dry = OceanMeshBuilder::new(radius, -200.0, terrain).with_subdivisions(4);
+        assert!(dry.build(&bounds, &data).is_none());
+    }
+}
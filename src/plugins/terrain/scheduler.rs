@@ -0,0 +1,181 @@
+use avian3d::math::{Scalar, Vector};
+use bevy::ecs::world::CommandQueue;
+use bevy::prelude::*;
+use bevy::tasks::Task;
+use bevy::utils::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use super::cube_tree::{ChunkHash, Observer};
+
+/// Builds a chunk, returning the commands that apply the result to the world, or `None` if the
+/// job was cancelled part way through.
+pub type ChunkWork = Box<dyn FnOnce(&CancellationToken) -> Option<CommandQueue> + Send + Sync>;
+
+/// A flag shared with a running chunk job, telling it that its result is no longer wanted.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A chunk build waiting in the [`ChunkJobQueue`].
+pub struct ChunkJob {
+    pub body: Entity,
+    pub hash: ChunkHash,
+    pub center: Vector,
+    pub size: Scalar,
+    work: ChunkWork,
+}
+
+impl ChunkJob {
+    pub fn new(
+        body: Entity,
+        hash: ChunkHash,
+        center: Vector,
+        size: Scalar,
+        work: ChunkWork,
+    ) -> Self {
+        Self {
+            body,
+            hash,
+            center,
+            size,
+            work,
+        }
+    }
+
+    /// Returns how urgently the chunk is needed by `observers`, higher first.
+    ///
+    /// This is the largest screen-space error the chunk's absence causes for any observer, so
+    /// big chunks close to an observer come first. Without observers, bigger chunks come first.
+    pub fn priority(&self, observers: &[Observer]) -> Scalar {
+        observers
+            .iter()
+            .map(|observer| observer.screen_space_error(self.center, self.size))
+            .reduce(Scalar::max)
+            .unwrap_or(self.size)
+    }
+
+    /// Runs the job, checking `cancellation` between its stages.
+    pub fn run(self, cancellation: &CancellationToken) -> Option<CommandQueue> {
+        if cancellation.is_cancelled() {
+            return None;
+        }
+        (self.work)(cancellation)
+    }
+}
+
+/// Chunk builds waiting for a free slot, at most one per chunk entity.
+///
+/// Jobs are started in order of [`ChunkJob::priority`], and only while fewer than
+/// [`TerrainPluginConfig::max_chunk_jobs`](super::TerrainPluginConfig::max_chunk_jobs) are
+/// running.
+#[derive(Resource, Default)]
+pub struct ChunkJobQueue {
+    jobs: HashMap<Entity, ChunkJob>,
+}
+
+impl ChunkJobQueue {
+    /// Queues a build for `chunk`, replacing any build of it that has not started yet.
+    pub fn push(&mut self, chunk: Entity, job: ChunkJob) {
+        self.jobs.insert(chunk, job);
+    }
+
+    /// Removes the build queued for `chunk`, if it has not started yet.
+    pub fn remove(&mut self, chunk: Entity) -> Option<ChunkJob> {
+        self.jobs.remove(&chunk)
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// Drops every queued job for which `keep` returns false.
+    pub fn retain(&mut self, mut keep: impl FnMut(Entity, &ChunkJob) -> bool) {
+        self.jobs.retain(|chunk, job| keep(*chunk, job));
+    }
+
+    /// Removes and returns up to `count` jobs, highest `priority` first.
+    pub fn pop_highest(
+        &mut self,
+        count: usize,
+        priority: impl Fn(&ChunkJob) -> Scalar,
+    ) -> Vec<(Entity, ChunkJob)> {
+        if count == 0 {
+            return Vec::new();
+        }
+
+        let mut ranked: Vec<(Scalar, Entity)> = self
+            .jobs
+            .iter()
+            .map(|(chunk, job)| (priority(job), *chunk))
+            .collect();
+        ranked.sort_unstable_by(|a, b| b.0.total_cmp(&a.0));
+        ranked
+            .into_iter()
+            .take(count)
+            .filter_map(|(_, chunk)| self.jobs.remove(&chunk).map(|job| (chunk, job)))
+            .collect()
+    }
+}
+
+/// A running chunk job. Removing the component, or despawning its chunk, cancels the job.
+#[derive(Component)]
+pub struct GenerateChunk {
+    pub task: Task<Option<CommandQueue>>,
+    cancellation: CancellationToken,
+}
+
+impl GenerateChunk {
+    pub fn new(task: Task<Option<CommandQueue>>, cancellation: CancellationToken) -> Self {
+        Self { task, cancellation }
+    }
+}
+
+impl Drop for GenerateChunk {
+    fn drop(&mut self) {
+        self.cancellation.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::terrain::cube_tree::Axis;
+
+    fn job(center: Vector, size: Scalar) -> ChunkJob {
+        let hash = ChunkHash::new_root(Axis::X);
+        ChunkJob::new(Entity::PLACEHOLDER, hash, center, size, Box::new(|_| None))
+    }
+
+    #[test]
+    fn test_pop_highest_prefers_large_and_near_chunks() {
+        let observers = [Observer::new(Vector::ZERO)];
+        let mut queue = ChunkJobQueue::default();
+        queue.push(Entity::from_raw(0), job(Vector::X * 100.0, 10.0));
+        queue.push(Entity::from_raw(1), job(Vector::X * 10.0, 10.0));
+        queue.push(Entity::from_raw(2), job(Vector::X * 100.0, 200.0));
+        // Requeueing a chunk replaces its pending job.
+        queue.push(Entity::from_raw(0), job(Vector::X * 1000.0, 10.0));
+
+        let popped: Vec<Entity> = queue
+            .pop_highest(2, |job| job.priority(&observers))
+            .into_iter()
+            .map(|(chunk, _)| chunk)
+            .collect();
+        assert_eq!(popped, [Entity::from_raw(2), Entity::from_raw(1)]);
+        assert_eq!(queue.len(), 1);
+    }
+}